@@ -1,26 +1,74 @@
+use crate::token::{Span, TokenType};
 use std::fmt;
 use std::fmt::Formatter;
 
-/// The parser chapter guides me to write the code in OOP,
-/// but it is easier to write and understand the code in Rust's ways,
-/// enumerating the data structure and matching expression.
+// The parser chapter guides me to write the code in OOP,
+// but it is easier to write and understand the code in Rust's ways,
+// enumerating the data structure and matching expression.
 
 /// Adhering to the parser chapter requires a token field.
 /// Still, I ignored creating it because Rust's enum can represent the Let statement,
 /// Return statement, or Expression statement, even without the token field.
-#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     LET { name: Expression, value: Expression },
     RETURN(Expression),
     EXPRESSION(Expression),
 }
 
-#[derive(Debug)]
+/// The expressions the evaluator can fail on carry a `Position`: that of the identifier,
+/// the operator, or the opening bracket.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
-    Identifier(String),
+    Identifier(String, Position),
     Integer(i64),
+    Boolean(bool),
+    StringLiteral(String),
+    Prefix {
+        op: String,
+        right: Box<Expression>,
+        position: Position,
+    },
+    Infix {
+        left: Box<Expression>,
+        op: String,
+        right: Box<Expression>,
+        position: Position,
+    },
+    If {
+        condition: Box<Expression>,
+        consequence: BlockStatement,
+        alternative: Option<BlockStatement>,
+    },
+    Function {
+        parameters: Vec<Expression>,
+        body: BlockStatement,
+    },
+    Call {
+        function: Box<Expression>,
+        arguments: Vec<Expression>,
+        position: Position,
+    },
+    Array(Vec<Expression>),
+    /// Key-value pairs in source order.
+    Hash(Vec<(Expression, Expression)>, Position),
+    Index {
+        left: Box<Expression>,
+        index: Box<Expression>,
+        position: Position,
+    },
 }
-#[derive(Debug)]
+
+/// Where a node is in the source, for runtime errors.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position(pub Span);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStatement {
+    pub statements: Vec<Statement>,
+}
+#[derive(Debug, Clone, PartialEq)]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
@@ -37,9 +85,76 @@ impl fmt::Display for Statement {
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Expression::Identifier(x) => write!(f, "{}", x),
+            Expression::Identifier(x, _) => write!(f, "{}", x),
             Expression::Integer(value) => write!(f, "{}", value),
+            Expression::Boolean(value) => write!(f, "{}", value),
+            Expression::StringLiteral(value) => write!(f, "\"{}\"", escape(value)),
+            Expression::Prefix { op, right, .. } => write!(f, "({}{})", op, right),
+            Expression::Infix {
+                left, op, right, ..
+            } => write!(f, "({} {} {})", left, op, right),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                write!(f, "if {} {}", condition, consequence)?;
+                if let Some(alternative) = alternative {
+                    write!(f, " else {}", alternative)?;
+                }
+                Ok(())
+            }
+            Expression::Function { parameters, body } => {
+                write!(f, "fn({}) {}", join(parameters), body)
+            }
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => write!(f, "{}({})", function, join(arguments)),
+            Expression::Array(elements) => write!(f, "[{}]", join(elements)),
+            Expression::Hash(pairs, _) => write!(
+                f,
+                "{{{}}}",
+                pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Expression::Index { left, index, .. } => write!(f, "({}[{}])", left, index),
+        }
+    }
+}
+/// The inverse of the escape sequences the lexer understands.
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for ch in value.chars() {
+        match ch {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            ch if ch.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+fn join(expressions: &[Expression]) -> String {
+    expressions
+        .iter()
+        .map(|expression| expression.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+        for statement in self.statements.iter() {
+            write!(f, " {}", statement)?;
         }
+        write!(f, " }}")
     }
 }
 impl fmt::Display for Program {
@@ -51,24 +166,125 @@ impl fmt::Display for Program {
     }
 }
 
+/// The program with every position reset, for comparing trees parsed from different layouts.
+#[cfg(test)]
+pub fn strip_positions(program: &Program) -> Program {
+    Program {
+        statements: program.statements.iter().map(strip_statement).collect(),
+    }
+}
+#[cfg(test)]
+fn strip_statement(statement: &Statement) -> Statement {
+    match statement {
+        Statement::LET { name, value } => Statement::LET {
+            name: strip_expression(name),
+            value: strip_expression(value),
+        },
+        Statement::RETURN(value) => Statement::RETURN(strip_expression(value)),
+        Statement::EXPRESSION(value) => Statement::EXPRESSION(strip_expression(value)),
+    }
+}
+#[cfg(test)]
+fn strip_block(block: &BlockStatement) -> BlockStatement {
+    BlockStatement {
+        statements: block.statements.iter().map(strip_statement).collect(),
+    }
+}
+#[cfg(test)]
+fn strip_expression(expression: &Expression) -> Expression {
+    let boxed = |expression: &Expression| Box::new(strip_expression(expression));
+    let all = |expressions: &[Expression]| expressions.iter().map(strip_expression).collect();
+    match expression {
+        Expression::Identifier(name, _) => {
+            Expression::Identifier(name.clone(), Position::default())
+        }
+        Expression::Integer(_) | Expression::Boolean(_) | Expression::StringLiteral(_) => {
+            expression.clone()
+        }
+        Expression::Prefix { op, right, .. } => Expression::Prefix {
+            op: op.clone(),
+            right: boxed(right),
+            position: Position::default(),
+        },
+        Expression::Infix {
+            left, op, right, ..
+        } => Expression::Infix {
+            left: boxed(left),
+            op: op.clone(),
+            right: boxed(right),
+            position: Position::default(),
+        },
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => Expression::If {
+            condition: boxed(condition),
+            consequence: strip_block(consequence),
+            alternative: alternative.as_ref().map(strip_block),
+        },
+        Expression::Function { parameters, body } => Expression::Function {
+            parameters: all(parameters),
+            body: strip_block(body),
+        },
+        Expression::Call {
+            function,
+            arguments,
+            ..
+        } => Expression::Call {
+            function: boxed(function),
+            arguments: all(arguments),
+            position: Position::default(),
+        },
+        Expression::Array(elements) => Expression::Array(all(elements)),
+        Expression::Hash(pairs, _) => Expression::Hash(
+            pairs
+                .iter()
+                .map(|(key, value)| (strip_expression(key), strip_expression(value)))
+                .collect(),
+            Position::default(),
+        ),
+        Expression::Index { left, index, .. } => Expression::Index {
+            left: boxed(left),
+            index: boxed(index),
+            position: Position::default(),
+        },
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::ast::{Expression, Program, Statement};
+    use crate::ast::{Expression, Position, Program, Statement};
 
     #[test]
     fn test_string() {
         let program = Program {
             statements: vec![Statement::LET {
-                name: Expression::Identifier("myVar".to_string()),
-                value: Expression::Identifier("anotherVar".to_string()),
+                name: Expression::Identifier("myVar".to_string(), Position::default()),
+                value: Expression::Identifier("anotherVar".to_string(), Position::default()),
             }],
         };
         assert_eq!("let myVar = anotherVar;\n", program.to_string())
     }
+
+    #[test]
+    fn test_operator_string() {
+        let expression = Expression::Infix {
+            left: Box::new(Expression::Prefix {
+                op: "-".to_string(),
+                right: Box::new(Expression::Identifier("a".to_string(), Position::default())),
+                position: Position::default(),
+            }),
+            op: "*".to_string(),
+            right: Box::new(Expression::Boolean(true)),
+            position: Position::default(),
+        };
+        assert_eq!("((-a) * true)", expression.to_string())
+    }
 }
 
-#[allow(dead_code)]
-#[derive(Debug, PartialEq, PartialOrd)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum Precedence {
     LOWEST,
     EQUALS,      // ==
@@ -79,3 +295,17 @@ pub enum Precedence {
     CALL,        // my_cunction(x){}
     LBRACKET,    // []
 }
+
+impl Precedence {
+    pub fn of(token_type: &TokenType) -> Precedence {
+        match token_type {
+            TokenType::EQ | TokenType::NotEq => Precedence::EQUALS,
+            TokenType::LT | TokenType::GT => Precedence::LESSGREATER,
+            TokenType::PLUS | TokenType::MINUS => Precedence::SUM,
+            TokenType::SLASH | TokenType::ASTERISK => Precedence::PRODUCT,
+            TokenType::LPAREN => Precedence::CALL,
+            TokenType::LBRACKET => Precedence::LBRACKET,
+            _ => Precedence::LOWEST,
+        }
+    }
+}
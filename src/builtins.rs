@@ -0,0 +1,114 @@
+use crate::object::{BuiltinFunction, Object};
+
+/// Host-provided functions, looked up when a name is not bound in the environment.
+pub const BUILTINS: &[(&str, BuiltinFunction)] = &[
+    ("len", len),
+    ("puts", puts),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+];
+
+pub fn lookup(name: &str) -> Option<Object> {
+    BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(name, function)| Object::Builtin {
+            name,
+            function: *function,
+        })
+}
+
+fn check_argument_count(arguments: &[Object], want: usize) -> Option<Object> {
+    if arguments.len() != want {
+        return Some(Object::Error(
+            format!(
+                "wrong number of arguments. got={}, want={}",
+                arguments.len(),
+                want
+            ),
+            None,
+        ));
+    }
+    None
+}
+
+fn len(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_argument_count(&arguments, 1) {
+        return error;
+    }
+    match &arguments[0] {
+        Object::String(value) => Object::Integer(value.chars().count() as i64),
+        Object::Array(elements) => Object::Integer(elements.len() as i64),
+        x => Object::Error(
+            format!("argument to `len` not supported, got {}", x.type_name()),
+            None,
+        ),
+    }
+}
+
+fn puts(arguments: Vec<Object>) -> Object {
+    for argument in arguments {
+        println!("{}", argument);
+    }
+    Object::Null
+}
+
+fn first(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_argument_count(&arguments, 1) {
+        return error;
+    }
+    match &arguments[0] {
+        Object::Array(elements) => elements.first().cloned().unwrap_or(Object::Null),
+        x => Object::Error(
+            format!("argument to `first` must be ARRAY, got {}", x.type_name()),
+            None,
+        ),
+    }
+}
+
+fn last(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_argument_count(&arguments, 1) {
+        return error;
+    }
+    match &arguments[0] {
+        Object::Array(elements) => elements.last().cloned().unwrap_or(Object::Null),
+        x => Object::Error(
+            format!("argument to `last` must be ARRAY, got {}", x.type_name()),
+            None,
+        ),
+    }
+}
+
+fn rest(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_argument_count(&arguments, 1) {
+        return error;
+    }
+    match &arguments[0] {
+        Object::Array(elements) if elements.is_empty() => Object::Null,
+        Object::Array(elements) => Object::Array(elements[1..].to_vec()),
+        x => Object::Error(
+            format!("argument to `rest` must be ARRAY, got {}", x.type_name()),
+            None,
+        ),
+    }
+}
+
+/// Arrays are immutable, so `push` returns a new array.
+fn push(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_argument_count(&arguments, 2) {
+        return error;
+    }
+    match &arguments[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.clone();
+            elements.push(arguments[1].clone());
+            Object::Array(elements)
+        }
+        x => Object::Error(
+            format!("argument to `push` must be ARRAY, got {}", x.type_name()),
+            None,
+        ),
+    }
+}
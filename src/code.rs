@@ -0,0 +1,255 @@
+pub type Instructions = Vec<u8>;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Opcode {
+    OpConstant,
+    OpPop,
+    OpAdd,
+    OpSub,
+    OpMul,
+    OpDiv,
+    OpTrue,
+    OpFalse,
+    OpNull,
+    OpEqual,
+    OpNotEqual,
+    OpGreaterThan,
+    OpLessThan,
+    OpMinus,
+    OpBang,
+    OpJumpNotTruthy,
+    OpJump,
+    OpGetGlobal,
+    OpSetGlobal,
+    OpGetLocal,
+    OpSetLocal,
+    OpGetBuiltin,
+    OpGetFree,
+    OpCurrentClosure,
+    OpArray,
+    OpHash,
+    OpIndex,
+    OpCall,
+    OpReturnValue,
+    OpReturn,
+    OpClosure,
+}
+
+/// The name of an opcode and the width in bytes of each of its operands.
+#[derive(Debug, PartialEq)]
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+const OPCODES: &[Opcode] = &[
+    Opcode::OpConstant,
+    Opcode::OpPop,
+    Opcode::OpAdd,
+    Opcode::OpSub,
+    Opcode::OpMul,
+    Opcode::OpDiv,
+    Opcode::OpTrue,
+    Opcode::OpFalse,
+    Opcode::OpNull,
+    Opcode::OpEqual,
+    Opcode::OpNotEqual,
+    Opcode::OpGreaterThan,
+    Opcode::OpLessThan,
+    Opcode::OpMinus,
+    Opcode::OpBang,
+    Opcode::OpJumpNotTruthy,
+    Opcode::OpJump,
+    Opcode::OpGetGlobal,
+    Opcode::OpSetGlobal,
+    Opcode::OpGetLocal,
+    Opcode::OpSetLocal,
+    Opcode::OpGetBuiltin,
+    Opcode::OpGetFree,
+    Opcode::OpCurrentClosure,
+    Opcode::OpArray,
+    Opcode::OpHash,
+    Opcode::OpIndex,
+    Opcode::OpCall,
+    Opcode::OpReturnValue,
+    Opcode::OpReturn,
+    Opcode::OpClosure,
+];
+
+impl Opcode {
+    pub fn definition(&self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::OpConstant => ("OpConstant", &[2]),
+            Opcode::OpPop => ("OpPop", &[]),
+            Opcode::OpAdd => ("OpAdd", &[]),
+            Opcode::OpSub => ("OpSub", &[]),
+            Opcode::OpMul => ("OpMul", &[]),
+            Opcode::OpDiv => ("OpDiv", &[]),
+            Opcode::OpTrue => ("OpTrue", &[]),
+            Opcode::OpFalse => ("OpFalse", &[]),
+            Opcode::OpNull => ("OpNull", &[]),
+            Opcode::OpEqual => ("OpEqual", &[]),
+            Opcode::OpNotEqual => ("OpNotEqual", &[]),
+            Opcode::OpGreaterThan => ("OpGreaterThan", &[]),
+            Opcode::OpLessThan => ("OpLessThan", &[]),
+            Opcode::OpMinus => ("OpMinus", &[]),
+            Opcode::OpBang => ("OpBang", &[]),
+            Opcode::OpJumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::OpJump => ("OpJump", &[2]),
+            Opcode::OpGetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::OpSetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::OpGetLocal => ("OpGetLocal", &[1]),
+            Opcode::OpSetLocal => ("OpSetLocal", &[1]),
+            Opcode::OpGetBuiltin => ("OpGetBuiltin", &[1]),
+            Opcode::OpGetFree => ("OpGetFree", &[1]),
+            Opcode::OpCurrentClosure => ("OpCurrentClosure", &[]),
+            Opcode::OpArray => ("OpArray", &[2]),
+            Opcode::OpHash => ("OpHash", &[2]),
+            Opcode::OpIndex => ("OpIndex", &[]),
+            Opcode::OpCall => ("OpCall", &[1]),
+            Opcode::OpReturnValue => ("OpReturnValue", &[]),
+            Opcode::OpReturn => ("OpReturn", &[]),
+            // The constant index of the function and the number of free variables. An
+            // OpGetLocal, OpGetFree or OpCurrentClosure naming each variable to capture
+            // follows; OpClosure reads them instead of running them.
+            Opcode::OpClosure => ("OpClosure", &[2, 1]),
+        };
+        Definition {
+            name,
+            operand_widths,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        OPCODES.get(byte as usize).copied()
+    }
+}
+
+/// Encodes an instruction. Operands are big-endian.
+///
+/// Panics when an operand does not fit its width; see `try_make`, which the compiler uses.
+#[cfg(test)]
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    try_make(op, operands).unwrap_or_else(|message| panic!("{}", message))
+}
+
+/// Encodes an instruction, or fails when an operand does not fit its width.
+pub fn try_make(op: Opcode, operands: &[usize]) -> Result<Instructions, String> {
+    let definition = op.definition();
+
+    let mut instruction = vec![op as u8];
+    for (operand, width) in operands.iter().zip(definition.operand_widths) {
+        match width {
+            2 => match u16::try_from(*operand) {
+                Ok(operand) => instruction.extend_from_slice(&operand.to_be_bytes()),
+                Err(_) => return Err(operand_too_large(&definition, *operand, u16::MAX.into())),
+            },
+            1 => match u8::try_from(*operand) {
+                Ok(operand) => instruction.push(operand),
+                Err(_) => return Err(operand_too_large(&definition, *operand, u8::MAX.into())),
+            },
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+    }
+    Ok(instruction)
+}
+
+fn operand_too_large(definition: &Definition, operand: usize, max: usize) -> String {
+    format!(
+        "operand too large: {} takes at most {}, got {}",
+        definition.name, max, operand
+    )
+}
+
+/// Decodes the operands following an opcode and returns them with the number of bytes read.
+pub fn read_operands(definition: &Definition, instructions: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = vec![];
+    let mut offset = 0;
+
+    for width in definition.operand_widths {
+        match width {
+            2 => operands.push(read_u16(instructions, offset) as usize),
+            1 => operands.push(instructions[offset] as usize),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+        offset += width;
+    }
+    (operands, offset)
+}
+
+pub fn read_u16(instructions: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([instructions[offset], instructions[offset + 1]])
+}
+
+#[cfg(test)]
+mod test {
+    use crate::code::{make, read_operands, try_make, Opcode};
+
+    #[test]
+    fn test_make() {
+        let tests: [(Opcode, &[usize], &[u8]); 5] = [
+            (
+                Opcode::OpConstant,
+                &[65534],
+                &[Opcode::OpConstant as u8, 255, 254],
+            ),
+            (Opcode::OpAdd, &[], &[Opcode::OpAdd as u8]),
+            (Opcode::OpGetLocal, &[255], &[Opcode::OpGetLocal as u8, 255]),
+            (
+                Opcode::OpClosure,
+                &[65534, 255],
+                &[Opcode::OpClosure as u8, 255, 254, 255],
+            ),
+            (Opcode::OpCall, &[3], &[Opcode::OpCall as u8, 3]),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(make(op, operands), expected, "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_try_make() {
+        assert_eq!(
+            try_make(Opcode::OpConstant, &[65535]),
+            Ok(vec![Opcode::OpConstant as u8, 255, 255])
+        );
+        assert_eq!(
+            try_make(Opcode::OpConstant, &[65536]),
+            Err("operand too large: OpConstant takes at most 65535, got 65536".to_string())
+        );
+        assert_eq!(
+            try_make(Opcode::OpClosure, &[0, 256]),
+            Err("operand too large: OpClosure takes at most 255, got 256".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests: [(Opcode, &[usize], usize); 4] = [
+            (Opcode::OpConstant, &[65535], 2),
+            (Opcode::OpGetLocal, &[255], 1),
+            (Opcode::OpClosure, &[65535, 255], 3),
+            (Opcode::OpPop, &[], 0),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = make(op, operands);
+            let (read, n) = read_operands(&op.definition(), &instruction[1..]);
+            assert_eq!(n, bytes_read);
+            assert_eq!(read, operands);
+        }
+    }
+
+    #[test]
+    fn test_from_byte() {
+        for byte in 0..=u8::MAX {
+            match Opcode::from_byte(byte) {
+                Some(op) => assert_eq!(op as u8, byte),
+                None => assert!(byte > Opcode::OpClosure as u8),
+            }
+        }
+    }
+}
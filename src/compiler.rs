@@ -0,0 +1,1051 @@
+use crate::ast::{BlockStatement, Expression, Position, Program, Statement};
+use crate::builtins::BUILTINS;
+use crate::code::{try_make, Instructions, Opcode};
+use crate::object::{CompiledFunction, Object};
+use crate::symbol_table::{Symbol, SymbolScope, SymbolTable};
+use crate::token::Span;
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+
+/// The output of the compiler: the instructions of the main program and the constant pool.
+#[derive(Debug)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Object>,
+    /// The names of the global slots, for reporting unbound ones.
+    pub global_names: Vec<String>,
+    /// Where the instructions of the main program that can fail come from, by offset.
+    pub positions: Vec<(usize, Span)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+/// The instructions of the function being compiled, or of the main program.
+#[derive(Debug, Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+    positions: Vec<(usize, Span)>,
+}
+
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+    /// The globals the program being compiled binds. Functions may refer to them
+    /// before their `let`, since they are looked up when the function runs.
+    program_globals: HashSet<String>,
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        let mut symbol_table = SymbolTable::new();
+        for (index, (name, _)) in BUILTINS.iter().enumerate() {
+            symbol_table.define_builtin(index, name);
+        }
+        Compiler::new_with_state(symbol_table, vec![])
+    }
+
+    /// Continues with the globals and constants of an earlier compilation, as the REPL does.
+    pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Object>) -> Compiler {
+        Compiler {
+            constants,
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+            program_globals: HashSet::new(),
+        }
+    }
+
+    /// Hands the globals and constants back, to be passed to `new_with_state`.
+    pub fn into_state(self) -> (SymbolTable, Vec<Object>) {
+        (self.symbol_table, self.constants)
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: self.current_scope().instructions.clone(),
+            constants: self.constants.clone(),
+            global_names: self.symbol_table.definition_names(),
+            positions: self.current_scope().positions.clone(),
+        }
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<(), String> {
+        self.program_globals = global_names(&program.statements);
+        for statement in program.statements.iter() {
+            self.compile_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn compile_block_statement(&mut self, block: &BlockStatement) -> Result<(), String> {
+        for statement in block.statements.iter() {
+            self.compile_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        match statement {
+            Statement::EXPRESSION(expression) => {
+                self.compile_expression(expression)?;
+                self.emit(Opcode::OpPop, &[])?;
+            }
+            Statement::LET { name, value } => {
+                let name = match name {
+                    Expression::Identifier(name, _) => name,
+                    x => return Err(format!("cannot bind to {}", x)),
+                };
+                // The name is defined after the value is compiled, so `let x = x + 1;`
+                // reads the previous `x`. Functions see their own name through OpCurrentClosure.
+                match value {
+                    Expression::Function { parameters, body } => {
+                        self.compile_function(parameters, body, Some(name))?
+                    }
+                    value => self.compile_expression(value)?,
+                }
+                let symbol = self.symbol_table.define(name);
+                match symbol.scope {
+                    SymbolScope::Global => self.emit(Opcode::OpSetGlobal, &[symbol.index]),
+                    _ => self.emit(Opcode::OpSetLocal, &[symbol.index]),
+                }?;
+            }
+            Statement::RETURN(value) => {
+                self.compile_expression(value)?;
+                self.emit(Opcode::OpReturnValue, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), String> {
+        match expression {
+            Expression::Integer(value) => {
+                let index = self.add_constant(Object::Integer(*value));
+                self.emit(Opcode::OpConstant, &[index])?;
+            }
+            Expression::StringLiteral(value) => {
+                let index = self.add_constant(Object::String(value.clone()));
+                self.emit(Opcode::OpConstant, &[index])?;
+            }
+            Expression::Boolean(true) => {
+                self.emit(Opcode::OpTrue, &[])?;
+            }
+            Expression::Boolean(false) => {
+                self.emit(Opcode::OpFalse, &[])?;
+            }
+            Expression::Identifier(name, position) => {
+                let symbol = match self.symbol_table.resolve(name) {
+                    Some(symbol) => symbol,
+                    None if self.program_globals.contains(name) => {
+                        self.symbol_table.define_global(name)
+                    }
+                    None => return Err(format!("undefined variable {}", name)),
+                };
+                let offset = self.load_symbol(&symbol)?;
+                self.locate(offset, position);
+            }
+            Expression::Prefix {
+                op,
+                right,
+                position,
+            } => {
+                self.compile_expression(right)?;
+                let offset = match op.as_str() {
+                    "!" => self.emit(Opcode::OpBang, &[]),
+                    "-" => self.emit(Opcode::OpMinus, &[]),
+                    _ => return Err(format!("unknown operator: {}", op)),
+                }?;
+                self.locate(offset, position);
+            }
+            Expression::Infix {
+                left,
+                op,
+                right,
+                position,
+            } => {
+                self.compile_expression(left)?;
+                self.compile_expression(right)?;
+                let offset = match op.as_str() {
+                    "+" => self.emit(Opcode::OpAdd, &[]),
+                    "-" => self.emit(Opcode::OpSub, &[]),
+                    "*" => self.emit(Opcode::OpMul, &[]),
+                    "/" => self.emit(Opcode::OpDiv, &[]),
+                    ">" => self.emit(Opcode::OpGreaterThan, &[]),
+                    "<" => self.emit(Opcode::OpLessThan, &[]),
+                    "==" => self.emit(Opcode::OpEqual, &[]),
+                    "!=" => self.emit(Opcode::OpNotEqual, &[]),
+                    _ => return Err(format!("unknown operator: {}", op)),
+                }?;
+                self.locate(offset, position);
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.compile_expression(condition)?;
+                // The jump targets are patched once the branches are compiled.
+                let jump_not_truthy = self.emit(Opcode::OpJumpNotTruthy, &[9999])?;
+
+                self.compile_branch(consequence)?;
+                let jump = self.emit(Opcode::OpJump, &[9999])?;
+
+                let after_consequence = self.current_scope().instructions.len();
+                self.change_operand(jump_not_truthy, after_consequence)?;
+
+                match alternative {
+                    Some(alternative) => self.compile_branch(alternative)?,
+                    None => {
+                        self.emit(Opcode::OpNull, &[])?;
+                    }
+                }
+
+                let after_alternative = self.current_scope().instructions.len();
+                self.change_operand(jump, after_alternative)?;
+            }
+            Expression::Function { parameters, body } => {
+                self.compile_function(parameters, body, None)?;
+            }
+            Expression::Call {
+                function,
+                arguments,
+                position,
+            } => {
+                self.compile_expression(function)?;
+                for argument in arguments {
+                    self.compile_expression(argument)?;
+                }
+                let offset = self.emit(Opcode::OpCall, &[arguments.len()])?;
+                self.locate(offset, position);
+            }
+            Expression::Array(elements) => {
+                for element in elements {
+                    self.compile_expression(element)?;
+                }
+                self.emit(Opcode::OpArray, &[elements.len()])?;
+            }
+            Expression::Hash(pairs, position) => {
+                for (key, value) in pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                let offset = self.emit(Opcode::OpHash, &[pairs.len() * 2])?;
+                self.locate(offset, position);
+            }
+            Expression::Index {
+                left,
+                index,
+                position,
+            } => {
+                self.compile_expression(left)?;
+                self.compile_expression(index)?;
+                let offset = self.emit(Opcode::OpIndex, &[])?;
+                self.locate(offset, position);
+            }
+        }
+        Ok(())
+    }
+
+    /// A branch of an `if` leaves its value on the stack, `null` when it has none.
+    fn compile_branch(&mut self, block: &BlockStatement) -> Result<(), String> {
+        self.compile_block_statement(block)?;
+
+        if self.last_instruction_is(Opcode::OpPop) {
+            self.remove_last_pop();
+        } else {
+            self.emit(Opcode::OpNull, &[])?;
+        }
+        Ok(())
+    }
+
+    fn compile_function(
+        &mut self,
+        parameters: &[Expression],
+        body: &BlockStatement,
+        name: Option<&str>,
+    ) -> Result<(), String> {
+        self.enter_scope();
+
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+        for parameter in parameters {
+            match parameter {
+                Expression::Identifier(name, _) => self.symbol_table.define(name),
+                x => return Err(format!("cannot bind to {}", x)),
+            };
+        }
+
+        self.compile_block_statement(body)?;
+
+        if self.last_instruction_is(Opcode::OpPop) {
+            self.replace_last_pop_with_return();
+        }
+        if !self.last_instruction_is(Opcode::OpReturnValue) {
+            self.emit(Opcode::OpReturn, &[])?;
+        }
+
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let local_names = self.symbol_table.definition_names();
+        let scope = self.leave_scope();
+
+        let function = CompiledFunction {
+            instructions: scope.instructions,
+            local_names,
+            free_names: free_symbols
+                .iter()
+                .map(|symbol| symbol.name.clone())
+                .collect(),
+            num_parameters: parameters.len(),
+            positions: scope.positions,
+            source: Expression::Function {
+                parameters: parameters.to_vec(),
+                body: body.clone(),
+            }
+            .to_string(),
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(function)));
+        self.emit(Opcode::OpClosure, &[index, free_symbols.len()])?;
+        // The variables to capture follow as the instructions that would load them.
+        for symbol in free_symbols.iter() {
+            self.load_symbol(symbol)?;
+        }
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> Result<usize, String> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::OpGetGlobal, &[symbol.index]),
+            SymbolScope::Local => self.emit(Opcode::OpGetLocal, &[symbol.index]),
+            SymbolScope::Builtin => self.emit(Opcode::OpGetBuiltin, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::OpGetFree, &[symbol.index]),
+            SymbolScope::Function => self.emit(Opcode::OpCurrentClosure, &[]),
+        }
+    }
+
+    fn add_constant(&mut self, object: Object) -> usize {
+        self.constants.push(object);
+        self.constants.len() - 1
+    }
+
+    /// Appends an instruction to the current scope and returns its position.
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> Result<usize, String> {
+        let instruction = try_make(op, operands)?;
+        let scope = self.current_scope_mut();
+        let position = scope.instructions.len();
+        scope.instructions.extend(instruction);
+
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction {
+            opcode: op,
+            position,
+        });
+        Ok(position)
+    }
+
+    /// Records that the instruction at `offset` in the current scope, which can fail at
+    /// runtime, comes from the expression at `position`.
+    fn locate(&mut self, offset: usize, position: &Position) {
+        self.current_scope_mut()
+            .positions
+            .push((offset, position.0));
+    }
+
+    fn last_instruction_is(&self, op: Opcode) -> bool {
+        matches!(
+            self.current_scope().last_instruction,
+            Some(EmittedInstruction { opcode, .. }) if opcode == op
+        )
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.current_scope_mut();
+        if let Some(last) = scope.last_instruction {
+            scope.instructions.truncate(last.position);
+            scope.last_instruction = scope.previous_instruction;
+        }
+    }
+
+    fn replace_last_pop_with_return(&mut self) {
+        let scope = self.current_scope_mut();
+        if let Some(last) = scope.last_instruction.as_mut() {
+            scope.instructions[last.position] = Opcode::OpReturnValue as u8;
+            last.opcode = Opcode::OpReturnValue;
+        }
+    }
+
+    fn change_operand(&mut self, position: usize, operand: usize) -> Result<(), String> {
+        let op = Opcode::from_byte(self.current_scope().instructions[position])
+            .expect("a valid opcode at an emitted position");
+        let instruction = try_make(op, &[operand])?;
+
+        let instructions = &mut self.current_scope_mut().instructions;
+        instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+        Ok(())
+    }
+
+    fn current_scope(&self) -> &CompilationScope {
+        self.scopes.last().expect("the main scope is never left")
+    }
+
+    fn current_scope_mut(&mut self) -> &mut CompilationScope {
+        self.scopes
+            .last_mut()
+            .expect("the main scope is never left")
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        let outer = mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    fn leave_scope(&mut self) -> CompilationScope {
+        let scope = self.scopes.pop().expect("a function scope to leave");
+        let outer = self
+            .symbol_table
+            .outer
+            .take()
+            .expect("an enclosing symbol table");
+        self.symbol_table = *outer;
+        scope
+    }
+}
+
+/// The names the statements bind globally: those of their `let`s, including the ones in
+/// the blocks of `if` statements, which do not open a scope of their own.
+fn global_names(statements: &[Statement]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for statement in statements {
+        match statement {
+            Statement::LET {
+                name: Expression::Identifier(name, _),
+                ..
+            } => {
+                names.insert(name.clone());
+            }
+            Statement::EXPRESSION(Expression::If {
+                consequence,
+                alternative,
+                ..
+            }) => {
+                names.extend(global_names(&consequence.statements));
+                if let Some(alternative) = alternative {
+                    names.extend(global_names(&alternative.statements));
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use crate::code::{make, Instructions, Opcode};
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::object::Object;
+    use crate::parser::Parser;
+
+    enum Constant {
+        Integer(i64),
+        String(&'static str),
+        Function(Vec<Instructions>),
+    }
+
+    fn compile(input: &str) -> Compiler {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        let program = parser.parse_program().unwrap();
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+        compiler
+    }
+
+    fn run_compiler_tests(tests: Vec<(&str, Vec<Constant>, Vec<Instructions>)>) {
+        for (input, expected_constants, expected_instructions) in tests {
+            let bytecode = compile(input).bytecode();
+
+            assert_eq!(
+                bytecode.instructions,
+                expected_instructions.concat(),
+                "wrong instructions for {}",
+                input
+            );
+            assert_eq!(
+                bytecode.constants.len(),
+                expected_constants.len(),
+                "wrong number of constants for {}",
+                input
+            );
+            for (constant, expected) in bytecode.constants.iter().zip(expected_constants) {
+                match (constant, expected) {
+                    (Object::Integer(value), Constant::Integer(expected)) => {
+                        assert_eq!(*value, expected, "{}", input)
+                    }
+                    (Object::String(value), Constant::String(expected)) => {
+                        assert_eq!(value, expected, "{}", input)
+                    }
+                    (Object::CompiledFunction(function), Constant::Function(expected)) => {
+                        assert_eq!(function.instructions, expected.concat(), "{}", input)
+                    }
+                    (x, _) => panic!("unexpected constant {:?} for {}", x, input),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        run_compiler_tests(vec![
+            (
+                "1 + 2",
+                vec![Constant::Integer(1), Constant::Integer(2)],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpAdd, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "1; 2",
+                vec![Constant::Integer(1), Constant::Integer(2)],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpPop, &[]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "1 - 2 * 3 / 4",
+                vec![
+                    Constant::Integer(1),
+                    Constant::Integer(2),
+                    Constant::Integer(3),
+                    Constant::Integer(4),
+                ],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpConstant, &[2]),
+                    make(Opcode::OpMul, &[]),
+                    make(Opcode::OpConstant, &[3]),
+                    make(Opcode::OpDiv, &[]),
+                    make(Opcode::OpSub, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "-1",
+                vec![Constant::Integer(1)],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpMinus, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        run_compiler_tests(vec![
+            (
+                "true",
+                vec![],
+                vec![make(Opcode::OpTrue, &[]), make(Opcode::OpPop, &[])],
+            ),
+            (
+                "1 > 2",
+                vec![Constant::Integer(1), Constant::Integer(2)],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpGreaterThan, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "1 < 2",
+                vec![Constant::Integer(1), Constant::Integer(2)],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpLessThan, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "true != false == true",
+                vec![],
+                vec![
+                    make(Opcode::OpTrue, &[]),
+                    make(Opcode::OpFalse, &[]),
+                    make(Opcode::OpNotEqual, &[]),
+                    make(Opcode::OpTrue, &[]),
+                    make(Opcode::OpEqual, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "!true",
+                vec![],
+                vec![
+                    make(Opcode::OpTrue, &[]),
+                    make(Opcode::OpBang, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_conditionals() {
+        run_compiler_tests(vec![
+            (
+                "if (true) { 10 }; 3333;",
+                vec![Constant::Integer(10), Constant::Integer(3333)],
+                vec![
+                    // 0000
+                    make(Opcode::OpTrue, &[]),
+                    // 0001
+                    make(Opcode::OpJumpNotTruthy, &[10]),
+                    // 0004
+                    make(Opcode::OpConstant, &[0]),
+                    // 0007
+                    make(Opcode::OpJump, &[11]),
+                    // 0010
+                    make(Opcode::OpNull, &[]),
+                    // 0011
+                    make(Opcode::OpPop, &[]),
+                    // 0012
+                    make(Opcode::OpConstant, &[1]),
+                    // 0015
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "if (true) { 10 } else { 20 }; 3333;",
+                vec![
+                    Constant::Integer(10),
+                    Constant::Integer(20),
+                    Constant::Integer(3333),
+                ],
+                vec![
+                    // 0000
+                    make(Opcode::OpTrue, &[]),
+                    // 0001
+                    make(Opcode::OpJumpNotTruthy, &[10]),
+                    // 0004
+                    make(Opcode::OpConstant, &[0]),
+                    // 0007
+                    make(Opcode::OpJump, &[13]),
+                    // 0010
+                    make(Opcode::OpConstant, &[1]),
+                    // 0013
+                    make(Opcode::OpPop, &[]),
+                    // 0014
+                    make(Opcode::OpConstant, &[2]),
+                    // 0017
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "if (true) { let a = 1; }",
+                vec![Constant::Integer(1)],
+                vec![
+                    // 0000
+                    make(Opcode::OpTrue, &[]),
+                    // 0001
+                    make(Opcode::OpJumpNotTruthy, &[14]),
+                    // 0004
+                    make(Opcode::OpConstant, &[0]),
+                    // 0007
+                    make(Opcode::OpSetGlobal, &[0]),
+                    // 0010
+                    make(Opcode::OpNull, &[]),
+                    // 0011
+                    make(Opcode::OpJump, &[15]),
+                    // 0014
+                    make(Opcode::OpNull, &[]),
+                    // 0015
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        run_compiler_tests(vec![
+            (
+                "let one = 1; let two = 2;",
+                vec![Constant::Integer(1), Constant::Integer(2)],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpSetGlobal, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpSetGlobal, &[1]),
+                ],
+            ),
+            (
+                "let one = 1; one;",
+                vec![Constant::Integer(1)],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpSetGlobal, &[0]),
+                    make(Opcode::OpGetGlobal, &[0]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "let one = 1; let one = one + 1;",
+                vec![Constant::Integer(1), Constant::Integer(1)],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpSetGlobal, &[0]),
+                    make(Opcode::OpGetGlobal, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpAdd, &[]),
+                    make(Opcode::OpSetGlobal, &[0]),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_string_array_hash_and_index_expressions() {
+        run_compiler_tests(vec![
+            (
+                r#""mon" + "key""#,
+                vec![Constant::String("mon"), Constant::String("key")],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpAdd, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "[]",
+                vec![],
+                vec![make(Opcode::OpArray, &[0]), make(Opcode::OpPop, &[])],
+            ),
+            (
+                "[1, 2][0]",
+                vec![
+                    Constant::Integer(1),
+                    Constant::Integer(2),
+                    Constant::Integer(0),
+                ],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpArray, &[2]),
+                    make(Opcode::OpConstant, &[2]),
+                    make(Opcode::OpIndex, &[]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "{2: 3, 1: 4}",
+                vec![
+                    Constant::Integer(2),
+                    Constant::Integer(3),
+                    Constant::Integer(1),
+                    Constant::Integer(4),
+                ],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpConstant, &[2]),
+                    make(Opcode::OpConstant, &[3]),
+                    make(Opcode::OpHash, &[4]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_functions() {
+        run_compiler_tests(vec![
+            (
+                "fn() { return 5 + 10 }",
+                vec![
+                    Constant::Integer(5),
+                    Constant::Integer(10),
+                    Constant::Function(vec![
+                        make(Opcode::OpConstant, &[0]),
+                        make(Opcode::OpConstant, &[1]),
+                        make(Opcode::OpAdd, &[]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                ],
+                vec![make(Opcode::OpClosure, &[2, 0]), make(Opcode::OpPop, &[])],
+            ),
+            (
+                "fn() { 1; 2 }",
+                vec![
+                    Constant::Integer(1),
+                    Constant::Integer(2),
+                    Constant::Function(vec![
+                        make(Opcode::OpConstant, &[0]),
+                        make(Opcode::OpPop, &[]),
+                        make(Opcode::OpConstant, &[1]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                ],
+                vec![make(Opcode::OpClosure, &[2, 0]), make(Opcode::OpPop, &[])],
+            ),
+            (
+                "fn() { }",
+                vec![Constant::Function(vec![make(Opcode::OpReturn, &[])])],
+                vec![make(Opcode::OpClosure, &[0, 0]), make(Opcode::OpPop, &[])],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_function_calls() {
+        run_compiler_tests(vec![
+            (
+                "let noArg = fn() { 24 }; noArg();",
+                vec![
+                    Constant::Integer(24),
+                    Constant::Function(vec![
+                        make(Opcode::OpConstant, &[0]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                ],
+                vec![
+                    make(Opcode::OpClosure, &[1, 0]),
+                    make(Opcode::OpSetGlobal, &[0]),
+                    make(Opcode::OpGetGlobal, &[0]),
+                    make(Opcode::OpCall, &[0]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "let manyArg = fn(a, b) { let c = a; c + b }; manyArg(24, 25);",
+                vec![
+                    Constant::Function(vec![
+                        make(Opcode::OpGetLocal, &[0]),
+                        make(Opcode::OpSetLocal, &[2]),
+                        make(Opcode::OpGetLocal, &[2]),
+                        make(Opcode::OpGetLocal, &[1]),
+                        make(Opcode::OpAdd, &[]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                    Constant::Integer(24),
+                    Constant::Integer(25),
+                ],
+                vec![
+                    make(Opcode::OpClosure, &[0, 0]),
+                    make(Opcode::OpSetGlobal, &[0]),
+                    make(Opcode::OpGetGlobal, &[0]),
+                    make(Opcode::OpConstant, &[1]),
+                    make(Opcode::OpConstant, &[2]),
+                    make(Opcode::OpCall, &[2]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_builtins() {
+        run_compiler_tests(vec![
+            (
+                "len([]); push([], 1);",
+                vec![Constant::Integer(1)],
+                vec![
+                    make(Opcode::OpGetBuiltin, &[0]),
+                    make(Opcode::OpArray, &[0]),
+                    make(Opcode::OpCall, &[1]),
+                    make(Opcode::OpPop, &[]),
+                    make(Opcode::OpGetBuiltin, &[5]),
+                    make(Opcode::OpArray, &[0]),
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpCall, &[2]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+            (
+                "let len = fn() { 1 }; len()",
+                vec![
+                    Constant::Integer(1),
+                    Constant::Function(vec![
+                        make(Opcode::OpConstant, &[0]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                ],
+                vec![
+                    make(Opcode::OpClosure, &[1, 0]),
+                    make(Opcode::OpSetGlobal, &[0]),
+                    make(Opcode::OpGetGlobal, &[0]),
+                    make(Opcode::OpCall, &[0]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_closures() {
+        run_compiler_tests(vec![
+            (
+                "fn(a) { fn(b) { fn(c) { a + b + c } } };",
+                vec![
+                    Constant::Function(vec![
+                        make(Opcode::OpGetFree, &[0]),
+                        make(Opcode::OpGetFree, &[1]),
+                        make(Opcode::OpAdd, &[]),
+                        make(Opcode::OpGetLocal, &[0]),
+                        make(Opcode::OpAdd, &[]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                    Constant::Function(vec![
+                        make(Opcode::OpClosure, &[0, 2]),
+                        make(Opcode::OpGetFree, &[0]),
+                        make(Opcode::OpGetLocal, &[0]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                    Constant::Function(vec![
+                        make(Opcode::OpClosure, &[1, 1]),
+                        make(Opcode::OpGetLocal, &[0]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                ],
+                vec![make(Opcode::OpClosure, &[2, 0]), make(Opcode::OpPop, &[])],
+            ),
+            (
+                "let x = 1; fn() { let y = x; let x = 2; y };",
+                vec![
+                    Constant::Integer(1),
+                    Constant::Integer(2),
+                    Constant::Function(vec![
+                        make(Opcode::OpGetGlobal, &[0]),
+                        make(Opcode::OpSetLocal, &[0]),
+                        make(Opcode::OpConstant, &[1]),
+                        make(Opcode::OpSetLocal, &[1]),
+                        make(Opcode::OpGetLocal, &[0]),
+                        make(Opcode::OpReturnValue, &[]),
+                    ]),
+                ],
+                vec![
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpSetGlobal, &[0]),
+                    make(Opcode::OpClosure, &[2, 0]),
+                    make(Opcode::OpPop, &[]),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        run_compiler_tests(vec![(
+            "let countDown = fn(x) { countDown(x - 1); }; countDown(1);",
+            vec![
+                Constant::Integer(1),
+                Constant::Function(vec![
+                    make(Opcode::OpCurrentClosure, &[]),
+                    make(Opcode::OpGetLocal, &[0]),
+                    make(Opcode::OpConstant, &[0]),
+                    make(Opcode::OpSub, &[]),
+                    make(Opcode::OpCall, &[1]),
+                    make(Opcode::OpReturnValue, &[]),
+                ]),
+                Constant::Integer(1),
+            ],
+            vec![
+                make(Opcode::OpClosure, &[1, 0]),
+                make(Opcode::OpSetGlobal, &[0]),
+                make(Opcode::OpGetGlobal, &[0]),
+                make(Opcode::OpConstant, &[2]),
+                make(Opcode::OpCall, &[1]),
+                make(Opcode::OpPop, &[]),
+            ],
+        )]);
+    }
+
+    #[test]
+    fn test_forward_references() {
+        run_compiler_tests(vec![(
+            "let later = fn() { value }; let value = 7;",
+            vec![
+                Constant::Function(vec![
+                    make(Opcode::OpGetGlobal, &[0]),
+                    make(Opcode::OpReturnValue, &[]),
+                ]),
+                Constant::Integer(7),
+            ],
+            vec![
+                make(Opcode::OpClosure, &[0, 0]),
+                make(Opcode::OpSetGlobal, &[1]),
+                make(Opcode::OpConstant, &[1]),
+                make(Opcode::OpSetGlobal, &[0]),
+            ],
+        )]);
+    }
+
+    #[test]
+    fn test_undefined_variables() {
+        let tests = [
+            ("foobar", "undefined variable foobar"),
+            ("let f = fn() { missing };", "undefined variable missing"),
+            (
+                "fn() { let f = fn() { y }; let y = 1; };",
+                "undefined variable y",
+            ),
+            ("let g = fn() { let z = 1; }; z", "undefined variable z"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(Lexer::new(input.to_string()));
+            let program = parser.parse_program().unwrap();
+            assert_eq!(
+                Compiler::new().compile(&program),
+                Err(expected.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_operands_too_large() {
+        let parameters: Vec<String> = (0..300).map(|i| format!("p{}", i)).collect();
+        let tests = [
+            (
+                format!("let f = fn() {{ 1 }}; f({});", vec!["1"; 300].join(", ")),
+                "operand too large: OpCall takes at most 255, got 300",
+            ),
+            (
+                format!("fn({}) {{ p299 }};", parameters.join(", ")),
+                "operand too large: OpGetLocal takes at most 255, got 299",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(Lexer::new(input.clone()));
+            let program = parser.parse_program().unwrap();
+            assert_eq!(
+                Compiler::new().compile(&program),
+                Err(expected.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Runs every script through both the evaluator and the virtual machine
+//! and checks that they agree with each other and with the expected output.
+
+use crate::compiler::Compiler;
+use crate::environment::Environment;
+use crate::evaluator::eval;
+use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::vm::{GLOBALS_SIZE, VM};
+use std::mem;
+
+const SCRIPTS: &[(&str, &str)] = &[
+    // Integers and booleans
+    ("5 + 5 * 2 - 10 / 2", "10"),
+    ("-(3 - 10) * 2", "14"),
+    ("1 < 2 == true", "true"),
+    ("1 > 2 != false", "false"),
+    ("!!5", "true"),
+    ("!0", "false"),
+    // Strings
+    (r#""mon" + "key""#, "monkey"),
+    (r#""a" == "a""#, "true"),
+    (r#""a" != "b""#, "true"),
+    // Conditionals
+    ("if (1 < 2) { 10 } else { 20 }", "10"),
+    ("if (false) { 10 }", "null"),
+    ("if ((if (false) { 10 })) { 10 } else { 20 }", "20"),
+    ("if (true) { let a = 1; }", "null"),
+    // Bindings
+    ("let a = 5; let b = a * 2; a + b", "15"),
+    ("let x = 1; let x = x + 1; x", "2"),
+    ("let x = 1; if (true) { let x = 2; } x", "2"),
+    ("let x = 1; let f = fn() { x }; let x = 2; f()", "2"),
+    ("fn() { let x = 1; let f = fn() { x }; let x = 2; f() }()", "2"),
+    // A `let` in a function shadows the enclosing binding only from where it runs.
+    ("let x = 1; fn() { let y = x; let x = 2; y }()", "1"),
+    ("let x = 1; fn() { let x = x + 1; x }()", "2"),
+    ("fn() { if (false) { let y = 1; } y }()", "ERROR: identifier not found: y"),
+    // Globals are looked up when a function runs, not when it is defined.
+    ("let later = fn() { value }; let value = 7; later()", "7"),
+    (
+        r#"
+        let isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } };
+        let isOdd = fn(n) { if (n == 0) { false } else { isEven(n - 1) } };
+        isEven(10)
+        "#,
+        "true",
+    ),
+    // Return
+    ("return 1; 2", "1"),
+    ("if (true) { if (true) { return 10; } return 1; }", "10"),
+    ("let f = fn() { if (true) { return 1; } 2 }; f()", "1"),
+    // Functions and closures
+    ("let add = fn(a, b) { a + b }; add(1, add(2, 3))", "6"),
+    ("fn() { }()", "null"),
+    ("fn(x) { x + 1 }", "fn(x) { (x + 1) }"),
+    ("let f = fn(x) { x }; [f]", "[fn(x) { x }]"),
+    ("fn(x) { let y = x; }(1)", "null"),
+    (
+        "let newAdder = fn(x) { fn(y) { x + y } }; let addTwo = newAdder(2); addTwo(3)",
+        "5",
+    ),
+    (
+        "let compose = fn(f, g) { fn(x) { g(f(x)) } }; compose(fn(x) { x + 1 }, fn(x) { x * 2 })(5)",
+        "12",
+    ),
+    (
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+        "610",
+    ),
+    (
+        r#"
+        let map = fn(arr, f) {
+            let iter = fn(arr, accumulated) {
+                if (len(arr) == 0) { accumulated } else { iter(rest(arr), push(accumulated, f(first(arr)))) }
+            };
+            iter(arr, []);
+        };
+        map([1, 2, 3], fn(x) { x * x })
+        "#,
+        "[1, 4, 9]",
+    ),
+    (
+        r#"
+        let reduce = fn(arr, initial, f) {
+            let iter = fn(arr, result) {
+                if (len(arr) == 0) { result } else { iter(rest(arr), f(result, first(arr))) }
+            };
+            iter(arr, initial);
+        };
+        reduce([1, 2, 3, 4, 5], 0, fn(sum, x) { sum + x })
+        "#,
+        "15",
+    ),
+    // Arrays and hashes
+    ("[1, 2 * 2, \"three\"]", "[1, 4, three]"),
+    ("[1, 2, 3][1 + 1]", "3"),
+    ("[1, 2, 3][3]", "null"),
+    ("[1, 2, 3][-1]", "null"),
+    (r#"{"one": 1, 2: true, false: "f"}"#, "{2: true, false: f, one: 1}"),
+    (r#"let h = {"a": 1}; h["a"] + h["b"]"#, "ERROR: type mismatch: INTEGER + NULL"),
+    (r#"{"a": [1, {"b": 2}]}["a"][1]["b"]"#, "2"),
+    ("{[1]: 1}", "ERROR: unusable as hash key: ARRAY"),
+    ("{1: 1}[[]]", "ERROR: unusable as hash key: ARRAY"),
+    // Builtins
+    (r#"len("hello") + len([1, 2])"#, "7"),
+    ("first([]) == last([])", "true"),
+    ("let len = fn(x) { 0 }; len([1])", "0"),
+    (r#"puts("conformance")"#, "null"),
+    // Errors
+    ("5 + true; 5", "ERROR: type mismatch: INTEGER + BOOLEAN"),
+    ("-true", "ERROR: unknown operator: -BOOLEAN"),
+    (r#""a" - "b""#, "ERROR: unknown operator: STRING - STRING"),
+    ("1 / 0", "ERROR: division by zero"),
+    (
+        "-9223372036854775807 - 2",
+        "ERROR: integer overflow: -9223372036854775807 - 2",
+    ),
+    ("1[0]", "ERROR: index operator not supported: INTEGER"),
+    ("1(2)", "ERROR: not a function: INTEGER"),
+    ("{fn() {}: 1}", "ERROR: unusable as hash key: FUNCTION"),
+    ("fn() {} == fn() {}", "ERROR: unknown operator: FUNCTION == FUNCTION"),
+    ("fn(x) { x }()", "ERROR: wrong number of arguments: want=1, got=0"),
+    ("let a = len(1); 2", "ERROR: argument to `len` not supported, got INTEGER"),
+    ("push([], 1, 2)", "ERROR: wrong number of arguments. got=3, want=2"),
+];
+
+/// Scripts the engines deliberately disagree on, with the output of the evaluator and
+/// then of the virtual machine. The compiler resolves a name when it compiles it, as the
+/// reference compiler does, while the evaluator looks names up as it runs.
+const DIFFERENCES: &[(&str, &str, &str)] = &[
+    // A name the program never binds globally is rejected before anything runs.
+    (
+        "if (null_value()) { 1 } else { 2 }",
+        "ERROR: identifier not found: null_value",
+        "ERROR: undefined variable null_value",
+    ),
+    (
+        "let later = fn() { missing }; later()",
+        "ERROR: identifier not found: missing",
+        "ERROR: undefined variable missing",
+    ),
+    // A local of an enclosing function is only visible after its `let`.
+    (
+        "fn() { let f = fn() { y }; let y = 3; f() }()",
+        "3",
+        "ERROR: undefined variable y",
+    ),
+    // A builtin stays the builtin where it was compiled before a global took its name,
+    // and a global of its name hides it wherever it was compiled after.
+    (
+        "let f = fn(x) { len(x) }; let len = fn(x) { 0 }; f([1])",
+        "0",
+        "1",
+    ),
+    (
+        "if (false) { let len = fn(x) { 0 }; } len([1])",
+        "1",
+        "ERROR: identifier not found: len",
+    ),
+];
+
+/// Inputs run one after another on the same bindings, as in the REPL, with the
+/// output expected after each.
+const SESSIONS: &[&[(&str, &str)]] = &[
+    &[
+        ("let later = fn() { value }; let value = 7; later()", "7"),
+        ("let value = 8; later()", "8"),
+    ],
+    &[
+        ("let f = fn(x) { len(x) }; f([1])", "1"),
+        ("let len = fn(x) { 0 }; len([1])", "0"),
+        ("len([1, 2])", "0"),
+    ],
+    &[
+        (
+            "let a = 1; let b = a + true;",
+            "ERROR: type mismatch: INTEGER + BOOLEAN",
+        ),
+        ("let c = a; a + c", "2"),
+    ],
+];
+
+fn parse(input: &str) -> crate::ast::Program {
+    let mut parser = Parser::new(Lexer::new(input.to_string()));
+    parser.parse_program().unwrap()
+}
+
+fn run_evaluator(input: &str) -> String {
+    eval(&parse(input), &mut Environment::new()).to_string()
+}
+
+fn run_vm(input: &str) -> String {
+    let mut compiler = Compiler::new();
+    if let Err(message) = compiler.compile(&parse(input)) {
+        return Object::Error(message, None).to_string();
+    }
+    let mut vm = VM::new(compiler.bytecode());
+    match vm.run() {
+        Ok(()) => vm.last_popped_stack_elem().to_string(),
+        Err(message) => Object::Error(message, None).to_string(),
+    }
+}
+
+#[test]
+fn test_engines_agree() {
+    for (input, expected) in SCRIPTS {
+        let evaluated = run_evaluator(input);
+        let executed = run_vm(input);
+        assert_eq!(evaluated, *expected, "evaluator output for {}", input);
+        assert_eq!(executed, *expected, "vm output for {}", input);
+    }
+}
+
+#[test]
+fn test_known_differences() {
+    for (input, evaluated, executed) in DIFFERENCES {
+        assert_eq!(
+            run_evaluator(input),
+            *evaluated,
+            "evaluator output for {}",
+            input
+        );
+        assert_eq!(run_vm(input), *executed, "vm output for {}", input);
+    }
+}
+
+#[test]
+fn test_engines_agree_on_error_positions() {
+    for (input, expected) in SCRIPTS {
+        if !expected.starts_with("ERROR") {
+            continue;
+        }
+        let evaluated = match eval(&parse(input), &mut Environment::new()) {
+            Object::Error(_, span) => span,
+            x => panic!("no error from the evaluator for {}. got={:?}", input, x),
+        };
+        let mut compiler = Compiler::new();
+        compiler.compile(&parse(input)).unwrap();
+        let mut vm = VM::new(compiler.bytecode());
+        assert!(vm.run().is_err(), "no error from the vm for {}", input);
+        assert!(evaluated.is_some(), "no position for {}", input);
+        assert_eq!(vm.position(), evaluated, "positions for {}", input);
+    }
+}
+
+#[test]
+fn test_engines_agree_across_inputs() {
+    for session in SESSIONS {
+        let mut env = Environment::new();
+        let mut state = Compiler::new().into_state();
+        let mut globals = vec![None; GLOBALS_SIZE];
+
+        for (input, expected) in session.iter() {
+            let program = parse(input);
+            let evaluated = eval(&program, &mut env).to_string();
+
+            let mut compiler = Compiler::new_with_state(state.0, state.1);
+            compiler.compile(&program).unwrap();
+            let bytecode = compiler.bytecode();
+            state = compiler.into_state();
+            let mut vm = VM::new_with_globals_store(bytecode, mem::take(&mut globals));
+            let executed = match vm.run() {
+                Ok(()) => vm.last_popped_stack_elem().to_string(),
+                Err(message) => Object::Error(message, None).to_string(),
+            };
+            globals = vm.into_globals_store();
+
+            assert_eq!(evaluated, *expected, "evaluator output for {}", input);
+            assert_eq!(executed, *expected, "vm output for {}", input);
+        }
+    }
+}
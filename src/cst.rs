@@ -0,0 +1,681 @@
+use crate::ast::{BlockStatement, Expression, Position, Program, Statement};
+use crate::lexer;
+use crate::lexer::Lexer;
+use crate::parse_error::ParseError;
+use crate::parser::Parser;
+use crate::token::{Span, Token, TokenType, TriviaKind};
+use std::fmt;
+use std::fmt::Formatter;
+use std::iter;
+use std::ops::Range;
+use std::rc::Rc;
+
+// A lossless syntax tree in the green/red style.
+//
+// The green tree owns the text. Its nodes only know their width, so an unchanged
+// subtree can be shared between edits. The red tree is a cursor over the green tree
+// that knows offsets and parents, which is what tools navigate.
+//
+// Every byte of the source, including whitespace, comments and code that failed to
+// parse, belongs to exactly one token, so printing the tree gives the source back.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Program,
+    LetStatement,
+    ReturnStatement,
+    ExpressionStatement,
+    Block,
+    Identifier,
+    /// An integer, string or boolean.
+    Literal,
+    PrefixExpression,
+    InfixExpression,
+    ParenExpression,
+    IfExpression,
+    ElseBranch,
+    FunctionLiteral,
+    ParameterList,
+    CallExpression,
+    ArgumentList,
+    ArrayLiteral,
+    HashLiteral,
+    HashPair,
+    IndexExpression,
+    /// Tokens skipped while recovering from a parse error.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenTrivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenToken {
+    pub kind: TokenType,
+    pub text: String,
+    pub leading_trivia: Vec<GreenTrivia>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenNode {
+    pub kind: NodeKind,
+    width: usize,
+    pub children: Vec<GreenElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenToken {
+    fn trivia_width(&self) -> usize {
+        self.leading_trivia
+            .iter()
+            .map(|trivia| trivia.text.len())
+            .sum()
+    }
+    /// The length in bytes, trivia included.
+    pub fn width(&self) -> usize {
+        self.trivia_width() + self.text.len()
+    }
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> GreenNode {
+        let width = children.iter().map(GreenElement::width).sum();
+        GreenNode {
+            kind,
+            width,
+            children,
+        }
+    }
+    /// The length in bytes, trivia included.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    /// The length of the trivia in front of the first token under the node.
+    fn leading_trivia_width(&self) -> usize {
+        for child in &self.children {
+            match child {
+                GreenElement::Token(token) => return token.trivia_width(),
+                GreenElement::Node(node) if node.width() > 0 => return node.leading_trivia_width(),
+                GreenElement::Node(_) => {}
+            }
+        }
+        0
+    }
+}
+
+impl GreenElement {
+    pub fn width(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.width(),
+            GreenElement::Token(token) => token.width(),
+        }
+    }
+}
+
+impl fmt::Display for GreenToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for trivia in &self.leading_trivia {
+            write!(f, "{}", trivia.text)?;
+        }
+        write!(f, "{}", self.text)
+    }
+}
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => write!(f, "{}", node)?,
+                GreenElement::Token(token) => write!(f, "{}", token)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A green node placed in the file: it knows where it starts and what contains it.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            offset: 0,
+            parent: None,
+        }))
+    }
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind
+    }
+    // Only tooling walks up the tree; the CLI's passes walk down.
+    #[allow(dead_code)]
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+    /// The bytes the node covers, including the trivia in front of its first token.
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.width()
+    }
+    pub fn children(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut children = vec![];
+
+        for child in &self.0.green.children {
+            children.push(match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    offset,
+                    parent: Some(self.clone()),
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: green.clone(),
+                    offset,
+                    parent: self.clone(),
+                }),
+            });
+            offset += child.width();
+        }
+        children
+    }
+    pub fn child_nodes(&self) -> Vec<SyntaxNode> {
+        self.children()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+    pub fn child_tokens(&self) -> Vec<SyntaxToken> {
+        self.children()
+            .into_iter()
+            .filter_map(|child| match child {
+                SyntaxElement::Node(_) => None,
+                SyntaxElement::Token(token) => Some(token),
+            })
+            .collect()
+    }
+    /// Where the node sits in the source whose lines are `lines`.
+    /// Unlike `text_range`, the span starts at the first token rather than at its trivia.
+    pub fn span(&self, lines: &LineIndex) -> Span {
+        let range = self.text_range();
+        lines.span(range.start + self.0.green.leading_trivia_width()..range.end)
+    }
+    /// Every token under the node in source order.
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = vec![];
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.descendant_tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> &TokenType {
+        &self.green.kind
+    }
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+    pub fn leading_trivia(&self) -> &[GreenTrivia] {
+        &self.green.leading_trivia
+    }
+    // Like `SyntaxNode::parent`, for tooling rather than the CLI.
+    #[allow(dead_code)]
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+    /// Where the token sits in the source whose lines are `lines`.
+    pub fn span(&self, lines: &LineIndex) -> Span {
+        lines.span(self.text_range())
+    }
+    /// The bytes of the token itself, without its trivia.
+    pub fn text_range(&self) -> Range<usize> {
+        let start = self.offset + self.green.trivia_width();
+        start..start + self.green.text.len()
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?}", self.kind(), self.text_range())
+    }
+}
+impl fmt::Display for SyntaxToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}@{:?} {:?}",
+            self.kind(),
+            self.text_range(),
+            self.text()
+        )
+    }
+}
+
+/// The result of parsing into a syntax tree. The tree is complete even when there are errors.
+pub struct Parse {
+    green: Rc<GreenNode>,
+    lines: LineIndex,
+    pub errors: Vec<ParseError>,
+}
+
+impl Parse {
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    /// The lines of the source the tree was built from, for the spans of its nodes and tokens.
+    pub fn line_index(&self) -> &LineIndex {
+        &self.lines
+    }
+
+    /// Builds the same `Program` the `Parser` would, as long as the source had no errors.
+    pub fn lower(&self) -> Result<Program, Vec<ParseError>> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
+        }
+        Ok(lower_program(&self.syntax(), &self.lines))
+    }
+}
+
+/// Parses `source` with the `Parser`, which records the syntax tree as it builds the AST.
+pub fn parse(source: &str) -> Parse {
+    let mut parser = Parser::with_syntax_tree(Lexer::with_trivia(source.to_string()));
+    let errors = parser.parse_program().err().unwrap_or_default();
+
+    Parse {
+        green: parser
+            .into_syntax_tree()
+            .expect("the parser was built with a syntax tree"),
+        lines: LineIndex::new(source),
+        errors,
+    }
+}
+
+/// Where each line of a source starts, so that byte offsets turn into lines and columns
+/// without rescanning the source from its beginning.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    source: String,
+    /// The byte offset each line starts at, in order.
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> LineIndex {
+        let line_starts = iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        LineIndex {
+            source: source.to_string(),
+            line_starts,
+        }
+    }
+
+    /// Turns a byte range of the source into a span with the line and column of its start.
+    pub fn span(&self, range: Range<usize>) -> Span {
+        let line = self
+            .line_starts
+            .partition_point(|&start| start <= range.start);
+        let line_start = self.line_starts[line - 1];
+        Span {
+            start: range.start,
+            end: range.end,
+            line,
+            column: self.source[line_start..range.start].chars().count() + 1,
+        }
+    }
+}
+
+/// Collects green elements into nodes as the parser opens and closes them.
+#[derive(Default)]
+pub struct Builder {
+    // The kind of each open node and where its children start in `children`.
+    parents: Vec<(NodeKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl Builder {
+    pub fn start_node(&mut self, kind: NodeKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+    /// Marks a position so that a node can later be opened around what follows it.
+    /// Infix expressions only know they are one after their left operand.
+    pub fn checkpoint(&self) -> usize {
+        self.children.len()
+    }
+    pub fn start_node_at(&mut self, checkpoint: usize, kind: NodeKind) {
+        self.parents.push((kind, checkpoint));
+    }
+    /// Adds a token, whose source text is `text`, to the innermost open node.
+    pub fn token(&mut self, token: &Token, text: &str) {
+        let green = GreenToken {
+            kind: token.token_type.clone(),
+            text: text.to_string(),
+            leading_trivia: token
+                .leading_trivia
+                .iter()
+                .map(|trivia| GreenTrivia {
+                    kind: trivia.kind,
+                    text: trivia.text.clone(),
+                })
+                .collect(),
+        };
+        self.children.push(GreenElement::Token(Rc::new(green)));
+    }
+    pub fn finish_node(&mut self) {
+        let (kind, first) = self.parents.pop().expect("no node to finish");
+        let children = self.children.split_off(first);
+        self.children
+            .push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+    }
+    pub fn depth(&self) -> usize {
+        self.parents.len()
+    }
+    pub fn finish(mut self) -> Rc<GreenNode> {
+        assert!(self.parents.is_empty(), "unfinished nodes");
+        match self.children.pop() {
+            Some(GreenElement::Node(node)) if self.children.is_empty() => node,
+            _ => panic!("the tree must have a single root"),
+        }
+    }
+}
+
+// Lowering walks a tree without errors, so every node has the children its kind promises.
+// `lines` are those of the text of the tree, which positions are worked out in.
+
+fn lower_program(node: &SyntaxNode, lines: &LineIndex) -> Program {
+    Program {
+        statements: node
+            .child_nodes()
+            .iter()
+            .map(|statement| lower_statement(statement, lines))
+            .collect(),
+    }
+}
+
+fn lower_statement(node: &SyntaxNode, lines: &LineIndex) -> Statement {
+    let children = node.child_nodes();
+    match node.kind() {
+        NodeKind::LetStatement => Statement::LET {
+            name: lower_expression(&children[0], lines),
+            value: lower_expression(&children[1], lines),
+        },
+        NodeKind::ReturnStatement => Statement::RETURN(lower_expression(&children[0], lines)),
+        _ => Statement::EXPRESSION(lower_expression(&children[0], lines)),
+    }
+}
+
+fn lower_block(node: &SyntaxNode, lines: &LineIndex) -> BlockStatement {
+    BlockStatement {
+        statements: node
+            .child_nodes()
+            .iter()
+            .map(|statement| lower_statement(statement, lines))
+            .collect(),
+    }
+}
+
+fn lower_expressions(node: &SyntaxNode, lines: &LineIndex) -> Vec<Expression> {
+    node.child_nodes()
+        .iter()
+        .map(|expression| lower_expression(expression, lines))
+        .collect()
+}
+
+fn lower_expression(node: &SyntaxNode, lines: &LineIndex) -> Expression {
+    let children = node.child_nodes();
+    let boxed = |index: usize| Box::new(lower_expression(&children[index], lines));
+    // The token a node is reported at is its first own token, except for calls,
+    // whose opening parenthesis starts the argument list.
+    let position = |node: &SyntaxNode| Position(first_token(node).span(lines));
+
+    match node.kind() {
+        NodeKind::Identifier => {
+            Expression::Identifier(first_token(node).text().to_string(), position(node))
+        }
+        NodeKind::Literal => lower_literal(&first_token(node)),
+        NodeKind::PrefixExpression => Expression::Prefix {
+            op: first_token(node).text().to_string(),
+            right: boxed(0),
+            position: position(node),
+        },
+        NodeKind::InfixExpression => Expression::Infix {
+            left: boxed(0),
+            op: first_token(node).text().to_string(),
+            right: boxed(1),
+            position: position(node),
+        },
+        NodeKind::ParenExpression => lower_expression(&children[0], lines),
+        NodeKind::IfExpression => Expression::If {
+            condition: boxed(0),
+            consequence: lower_block(&children[1], lines),
+            alternative: children
+                .get(2)
+                .map(|branch| lower_block(&branch.child_nodes()[0], lines)),
+        },
+        NodeKind::FunctionLiteral => Expression::Function {
+            parameters: lower_expressions(&children[0], lines),
+            body: lower_block(&children[1], lines),
+        },
+        NodeKind::CallExpression => Expression::Call {
+            function: boxed(0),
+            arguments: lower_expressions(&children[1], lines),
+            position: position(&children[1]),
+        },
+        NodeKind::ArrayLiteral => Expression::Array(lower_expressions(node, lines)),
+        NodeKind::HashLiteral => Expression::Hash(
+            children
+                .iter()
+                .map(|pair| {
+                    let pair = pair.child_nodes();
+                    (
+                        lower_expression(&pair[0], lines),
+                        lower_expression(&pair[1], lines),
+                    )
+                })
+                .collect(),
+            position(node),
+        ),
+        NodeKind::IndexExpression => Expression::Index {
+            left: boxed(0),
+            index: boxed(1),
+            position: position(node),
+        },
+        kind => unreachable!("{:?} is not an expression", kind),
+    }
+}
+
+/// The first token that belongs to the node itself rather than to a child node.
+fn first_token(node: &SyntaxNode) -> SyntaxToken {
+    node.children()
+        .into_iter()
+        .find_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+        .expect("a node with a token of its own")
+}
+
+fn lower_literal(token: &SyntaxToken) -> Expression {
+    match token.kind() {
+        TokenType::INT => Expression::Integer(token.text().parse().unwrap()),
+        TokenType::TRUE => Expression::Boolean(true),
+        TokenType::FALSE => Expression::Boolean(false),
+        // The tree keeps the quoted source text of the string.
+        _ => Expression::StringLiteral(lexer::string_value(token.text())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cst::{parse, LineIndex, NodeKind, SyntaxElement, SyntaxNode};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::token::{TokenType, TriviaKind};
+
+    const SOURCES: &[&str] = &[
+        "",
+        "let x = 5;",
+        "  // leading comment\nlet x = 5 /* five */ ;\n\n// trailing comment\n",
+        "let add = fn(a, b) { a + b; };\nadd(1, 2 * 3);",
+        "let x = if (a < b) { -a } else { !b };",
+        "return [1, \"two\\n\", true][0];",
+        "let h = {\"a\": 1, 2: false, true: fn() { 3 }};\nh[\"a\"]",
+        "((1 + 2) * 3 - 4 / 5) == 6 != (7 > 8)",
+        "let 名前 = \"值\"; 名前",
+        "a + add(b * c) + d; add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+        "a * [1, 2, 3, 4][b * c] * d; add(a * b[2], b[1], 2 * [1, 2][1])",
+        "-a * b; !-a; a + b * c + d / e - f; 3 + 4; -5 * 5",
+        "{}; []; fn() {}; if (x) {} else {}; fn(x, y, z) { return x; }(1, 2, 3)",
+    ];
+
+    #[test]
+    fn test_lossless() {
+        let broken = [
+            "let = 5;\nlet y = 1;",
+            "let f = fn(x y) { x };\n}\n",
+            "fn() { 1 + }; 2",
+            "let s = \"unterminated",
+            "let x = (1 + 2\nlet y = @;",
+            "/* unterminated comment",
+            "if (x) { 1",
+            "{if (a) { 1 } 2}; 3",
+        ];
+        for source in SOURCES.iter().chain(broken.iter()) {
+            let parse = parse(source);
+            assert_eq!(parse.syntax().to_string(), *source);
+            assert_eq!(parse.syntax().text_range(), 0..source.len());
+        }
+    }
+
+    #[test]
+    fn test_lower() {
+        for source in SOURCES {
+            let expected = Parser::new(Lexer::new(source.to_string()))
+                .parse_program()
+                .unwrap();
+            let lowered = parse(source).lower().unwrap();
+            assert_eq!(lowered, expected, "source: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = "let = 5;\nlet y = 1;\nlet z = fn(x) { x * };";
+        let parse = parse(source);
+        let messages: Vec<String> = parse.errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "expected next token to be IDENT, got ASSIGN instead",
+                "no prefix parse function for RBRACE found",
+            ]
+        );
+        assert!(parse.lower().is_err());
+
+        let kinds: Vec<NodeKind> = parse
+            .syntax()
+            .child_nodes()
+            .iter()
+            .map(SyntaxNode::kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                NodeKind::LetStatement,
+                NodeKind::LetStatement,
+                NodeKind::LetStatement
+            ]
+        );
+        let first = &parse.syntax().child_nodes()[0];
+        assert_eq!(first.child_nodes().last().unwrap().kind(), NodeKind::Error);
+        assert_eq!(first.child_nodes().last().unwrap().to_string(), "let = 5;");
+    }
+
+    #[test]
+    fn test_navigation() {
+        let source = "let x = 1;\nlet y = x /* add */ + 2;";
+        let root = parse(source).syntax();
+
+        let tokens = root.descendant_tokens();
+        let plus = tokens
+            .iter()
+            .find(|token| *token.kind() == TokenType::PLUS)
+            .unwrap();
+        assert_eq!(plus.text_range(), 31..32);
+        assert_eq!(plus.span(&LineIndex::new(source)).to_string(), "2:21");
+        let trivia: Vec<(TriviaKind, &str)> = plus
+            .leading_trivia()
+            .iter()
+            .map(|trivia| (trivia.kind, trivia.text.as_str()))
+            .collect();
+        assert_eq!(
+            trivia,
+            vec![
+                (TriviaKind::Whitespace, " "),
+                (TriviaKind::BlockComment, "/* add */"),
+                (TriviaKind::Whitespace, " "),
+            ]
+        );
+
+        let infix = plus.parent();
+        assert_eq!(infix.kind(), NodeKind::InfixExpression);
+        assert_eq!(infix.to_string(), " x /* add */ + 2");
+        let span = infix.span(&LineIndex::new(source));
+        assert_eq!(span.to_string(), "2:9");
+        assert_eq!(span.start..span.end, 19..34);
+        assert_eq!(infix.parent().unwrap().kind(), NodeKind::LetStatement);
+        assert_eq!(
+            infix.parent().unwrap().parent().unwrap().kind(),
+            NodeKind::Program
+        );
+
+        match &root.children()[2] {
+            SyntaxElement::Token(eof) => assert_eq!(*eof.kind(), TokenType::EOF),
+            x => panic!("last child is not the end of file. got={:?}", x),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::token::Span;
+
+// Errors are shown with the source line they point at, whether the parser or the
+// program running reported them.
+
+/// Renders `message` followed by the source line of `span` with the span underlined.
+pub fn render(message: &str, span: Span, source: &str) -> String {
+    render_at(message, span, &span.to_string(), source)
+}
+
+/// Like `render`, but points at `path:line:col` so the error can be found in a script file.
+pub fn render_in_file(message: &str, span: Span, path: &str, source: &str) -> String {
+    render_at(message, span, &format!("{}:{}", path, span), source)
+}
+
+fn render_at(message: &str, span: Span, location: &str, source: &str) -> String {
+    let line = source
+        .lines()
+        .nth(span.line.saturating_sub(1))
+        .unwrap_or("");
+    let gutter = " ".repeat(span.line.to_string().len());
+
+    // Only the first line of a multi-line token is shown. EOF has no width at all.
+    let width = source
+        .get(span.start..span.end)
+        .map(|text| text.lines().next().unwrap_or("").chars().count())
+        .unwrap_or(0)
+        .max(1);
+
+    format!(
+        "error: {}\n{}--> {}\n{} |\n{} | {}\n{} | {}{}\n",
+        message,
+        gutter,
+        location,
+        gutter,
+        span.line,
+        line,
+        gutter,
+        " ".repeat(span.column.saturating_sub(1)),
+        "^".repeat(width),
+    )
+}
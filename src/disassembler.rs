@@ -0,0 +1,128 @@
+use crate::code::{read_operands, Opcode};
+use crate::compiler::Bytecode;
+use crate::object::Object;
+
+/// Prints one instruction per line as its offset, opcode name and operands.
+///
+/// ```text
+/// 0000 OpConstant 1
+/// 0003 OpClosure 2 0
+/// ```
+pub fn disassemble_instructions(instructions: &[u8]) -> String {
+    let mut out = String::new();
+    let mut offset = 0;
+
+    while offset < instructions.len() {
+        let op = match Opcode::from_byte(instructions[offset]) {
+            Some(op) => op,
+            None => {
+                out.push_str(&format!(
+                    "{:04} ERROR: unknown opcode {}\n",
+                    offset, instructions[offset]
+                ));
+                offset += 1;
+                continue;
+            }
+        };
+        let definition = op.definition();
+        let (operands, read) = read_operands(&definition, &instructions[offset + 1..]);
+
+        out.push_str(&format!("{:04} {}", offset, definition.name));
+        for operand in operands {
+            out.push_str(&format!(" {}", operand));
+        }
+        out.push('\n');
+
+        offset += 1 + read;
+    }
+    out
+}
+
+/// Prints the main instructions followed by the constant pool,
+/// with the instructions of every compiled function indented below it.
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut out = disassemble_instructions(&bytecode.instructions);
+
+    if bytecode.constants.is_empty() {
+        return out;
+    }
+    out.push_str("\nconstants:\n");
+    for (index, constant) in bytecode.constants.iter().enumerate() {
+        match constant {
+            Object::CompiledFunction(function) => {
+                out.push_str(&format!(
+                    "{:04} fn (parameters={}, locals={})\n",
+                    index,
+                    function.num_parameters,
+                    function.local_names.len()
+                ));
+                for line in disassemble_instructions(&function.instructions).lines() {
+                    out.push_str(&format!("     {}\n", line));
+                }
+            }
+            Object::String(value) => out.push_str(&format!("{:04} {:?}\n", index, value)),
+            constant => out.push_str(&format!("{:04} {}\n", index, constant)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::code::{make, Opcode};
+    use crate::compiler::Compiler;
+    use crate::disassembler::{disassemble, disassemble_instructions};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_disassemble_instructions() {
+        let instructions = [
+            make(Opcode::OpAdd, &[]),
+            make(Opcode::OpGetLocal, &[1]),
+            make(Opcode::OpConstant, &[2]),
+            make(Opcode::OpConstant, &[65535]),
+            make(Opcode::OpClosure, &[65535, 255]),
+        ]
+        .concat();
+
+        assert_eq!(
+            disassemble_instructions(&instructions),
+            "0000 OpAdd\n\
+             0001 OpGetLocal 1\n\
+             0003 OpConstant 2\n\
+             0006 OpConstant 65535\n\
+             0009 OpClosure 65535 255\n"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_bytecode() {
+        let input = r#"let add = fn(a, b) { a + b }; add(1, "two");"#;
+        let program = Parser::new(Lexer::new(input.to_string()))
+            .parse_program()
+            .unwrap();
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+
+        assert_eq!(
+            disassemble(&compiler.bytecode()),
+            "0000 OpClosure 0 0\n\
+             0004 OpSetGlobal 0\n\
+             0007 OpGetGlobal 0\n\
+             0010 OpConstant 1\n\
+             0013 OpConstant 2\n\
+             0016 OpCall 2\n\
+             0018 OpPop\n\
+             \n\
+             constants:\n\
+             0000 fn (parameters=2, locals=2)\n     \
+             0000 OpGetLocal 0\n     \
+             0002 OpGetLocal 1\n     \
+             0004 OpAdd\n     \
+             0005 OpReturnValue\n\
+             0001 1\n\
+             0002 \"two\"\n"
+        );
+    }
+}
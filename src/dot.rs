@@ -0,0 +1,257 @@
+use crate::ast::{BlockStatement, Expression, Program, Statement};
+
+/// Renders the program as a Graphviz graph: one node per statement and expression,
+/// labeled with its keyword, operator or literal. Children keep their source order,
+/// and edges are labeled where the position alone does not tell the child's role.
+///
+/// ```text
+/// digraph ast {
+///     ordering=out;
+///     node [shape=box];
+///     n0 [label="program"];
+///     n1 [label="expression"];
+///     n0 -> n1;
+///     ...
+/// }
+/// ```
+pub fn program_to_dot(program: &Program) -> String {
+    let mut graph = Graph {
+        out: String::from("digraph ast {\n    ordering=out;\n    node [shape=box];\n"),
+        next_id: 0,
+    };
+    let root = graph.node("program");
+    for statement in &program.statements {
+        let child = graph.statement(statement);
+        graph.edge(root, child, None);
+    }
+    graph.out.push_str("}\n");
+    graph.out
+}
+
+struct Graph {
+    out: String,
+    next_id: usize,
+}
+
+impl Graph {
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.out
+            .push_str(&format!("    n{} [label=\"{}\"];\n", id, escape(label)));
+        id
+    }
+    fn edge(&mut self, from: usize, to: usize, label: Option<&str>) {
+        match label {
+            Some(label) => self.out.push_str(&format!(
+                "    n{} -> n{} [label=\"{}\"];\n",
+                from,
+                to,
+                escape(label)
+            )),
+            None => self.out.push_str(&format!("    n{} -> n{};\n", from, to)),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        match statement {
+            Statement::LET { name, value } => {
+                let id = self.node("let");
+                let name = self.expression(name);
+                self.edge(id, name, Some("name"));
+                let value = self.expression(value);
+                self.edge(id, value, Some("value"));
+                id
+            }
+            Statement::RETURN(value) => {
+                let id = self.node("return");
+                let value = self.expression(value);
+                self.edge(id, value, None);
+                id
+            }
+            Statement::EXPRESSION(expression) => {
+                let id = self.node("expression");
+                let expression = self.expression(expression);
+                self.edge(id, expression, None);
+                id
+            }
+        }
+    }
+    fn block(&mut self, block: &BlockStatement) -> usize {
+        let id = self.node("block");
+        for statement in &block.statements {
+            let child = self.statement(statement);
+            self.edge(id, child, None);
+        }
+        id
+    }
+    fn expression(&mut self, expression: &Expression) -> usize {
+        match expression {
+            // Literals print the way they are written, strings with their quotes.
+            Expression::Identifier(..)
+            | Expression::Integer(_)
+            | Expression::Boolean(_)
+            | Expression::StringLiteral(_) => self.node(&expression.to_string()),
+            Expression::Prefix { op, right, .. } => {
+                let id = self.node(op);
+                let right = self.expression(right);
+                self.edge(id, right, None);
+                id
+            }
+            Expression::Infix {
+                left, op, right, ..
+            } => {
+                let id = self.node(op);
+                let left = self.expression(left);
+                self.edge(id, left, None);
+                let right = self.expression(right);
+                self.edge(id, right, None);
+                id
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let id = self.node("if");
+                let condition = self.expression(condition);
+                self.edge(id, condition, Some("condition"));
+                let consequence = self.block(consequence);
+                self.edge(id, consequence, Some("consequence"));
+                if let Some(alternative) = alternative {
+                    let alternative = self.block(alternative);
+                    self.edge(id, alternative, Some("alternative"));
+                }
+                id
+            }
+            Expression::Function { parameters, body } => {
+                let id = self.node("fn");
+                for parameter in parameters {
+                    let parameter = self.expression(parameter);
+                    self.edge(id, parameter, Some("parameter"));
+                }
+                let body = self.block(body);
+                self.edge(id, body, Some("body"));
+                id
+            }
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => {
+                let id = self.node("call");
+                let function = self.expression(function);
+                self.edge(id, function, Some("function"));
+                for argument in arguments {
+                    let argument = self.expression(argument);
+                    self.edge(id, argument, Some("argument"));
+                }
+                id
+            }
+            Expression::Array(elements) => {
+                let id = self.node("[]");
+                for element in elements {
+                    let element = self.expression(element);
+                    self.edge(id, element, None);
+                }
+                id
+            }
+            Expression::Hash(pairs, _) => {
+                let id = self.node("{}");
+                for (key, value) in pairs {
+                    let pair = self.node(":");
+                    self.edge(id, pair, None);
+                    let key = self.expression(key);
+                    self.edge(pair, key, Some("key"));
+                    let value = self.expression(value);
+                    self.edge(pair, value, Some("value"));
+                }
+                id
+            }
+            Expression::Index { left, index, .. } => {
+                let id = self.node("index");
+                let left = self.expression(left);
+                self.edge(id, left, None);
+                let index = self.expression(index);
+                self.edge(id, index, Some("index"));
+                id
+            }
+        }
+    }
+}
+
+/// Escapes a label for a double-quoted DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dot::program_to_dot;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dot(input: &str) -> String {
+        let program = Parser::new(Lexer::new(String::from(input)))
+            .parse_program()
+            .unwrap();
+        program_to_dot(&program)
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(
+            dot("-1 + 2 * x;"),
+            "digraph ast {
+    ordering=out;
+    node [shape=box];
+    n0 [label=\"program\"];
+    n1 [label=\"expression\"];
+    n2 [label=\"+\"];
+    n3 [label=\"-\"];
+    n4 [label=\"1\"];
+    n3 -> n4;
+    n2 -> n3;
+    n5 [label=\"*\"];
+    n6 [label=\"2\"];
+    n5 -> n6;
+    n7 [label=\"x\"];
+    n5 -> n7;
+    n2 -> n5;
+    n1 -> n2;
+    n0 -> n1;
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_labels_and_edges() {
+        let graph =
+            dot("let f = fn(a) { if (a) { \"say \\\"hi\\\"\" } else { {true: [a][0]} } }; f(1)");
+        let lines: Vec<&str> = graph.lines().map(str::trim).collect();
+        let expected = [
+            "n1 [label=\"let\"];",
+            "n1 -> n2 [label=\"name\"];",
+            "n3 [label=\"fn\"];",
+            "n3 -> n4 [label=\"parameter\"];",
+            "n7 [label=\"if\"];",
+            "n7 -> n8 [label=\"condition\"];",
+            "n11 [label=\"\\\"say \\\\\\\"hi\\\\\\\"\\\"\"];",
+            "n7 -> n9 [label=\"consequence\"];",
+            "n14 [label=\"{}\"];",
+            "n15 [label=\":\"];",
+            "n15 -> n16 [label=\"key\"];",
+            "n17 [label=\"index\"];",
+            "n17 -> n20 [label=\"index\"];",
+            "n7 -> n12 [label=\"alternative\"];",
+            "n3 -> n5 [label=\"body\"];",
+            "n22 [label=\"call\"];",
+            "n22 -> n23 [label=\"function\"];",
+            "n22 -> n24 [label=\"argument\"];",
+        ];
+        for line in expected {
+            assert!(lines.contains(&line), "missing {} in\n{}", line, graph);
+        }
+    }
+}
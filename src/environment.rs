@@ -0,0 +1,40 @@
+use crate::object::Object;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Bindings created by `let` statements and function parameters.
+///
+/// The store is shared, so a cloned `Environment` sees bindings added after the clone.
+/// Function objects rely on this to call themselves recursively.
+/// Only function calls open a new scope; blocks of `if` bind into the current one.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    store: Rc<RefCell<HashMap<String, Object>>>,
+    outer: Option<Box<Environment>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn new_enclosed(outer: &Environment) -> Environment {
+        Environment {
+            store: Rc::default(),
+            outer: Some(Box::new(outer.clone())),
+        }
+    }
+
+    /// Looks the name up in this scope first, then in the enclosing ones.
+    pub fn get(&self, name: &str) -> Option<Object> {
+        if let Some(value) = self.store.borrow().get(name) {
+            return Some(value.clone());
+        }
+        self.outer.as_ref().and_then(|outer| outer.get(name))
+    }
+
+    pub fn set(&mut self, name: &str, value: Object) {
+        self.store.borrow_mut().insert(name.to_string(), value);
+    }
+}
@@ -0,0 +1,876 @@
+use crate::ast::{BlockStatement, Expression, Position, Program, Statement};
+use crate::builtins;
+use crate::environment::Environment;
+use crate::object::Object;
+use crate::vm::MAX_FRAMES;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+/// Function calls nest at most this deep, as in the VM, where the main program takes a frame too.
+const MAX_CALL_DEPTH: usize = MAX_FRAMES - 1;
+
+thread_local! {
+    /// The number of function calls currently being evaluated.
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn eval(program: &Program, env: &mut Environment) -> Object {
+    let mut result = Object::Null;
+
+    for statement in program.statements.iter() {
+        result = eval_statement(statement, env);
+
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error(..) => return result,
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Unlike `eval`, a block keeps `ReturnValue` wrapped,
+/// so that a `return` in a nested block also stops the outer blocks.
+fn eval_block_statement(block: &BlockStatement, env: &mut Environment) -> Object {
+    let mut result = Object::Null;
+
+    for statement in block.statements.iter() {
+        result = eval_statement(statement, env);
+
+        if matches!(result, Object::ReturnValue(_) | Object::Error(..)) {
+            return result;
+        }
+    }
+    result
+}
+
+fn eval_statement(statement: &Statement, env: &mut Environment) -> Object {
+    match statement {
+        Statement::EXPRESSION(expression) => eval_expression(expression, env),
+        Statement::RETURN(expression) => {
+            let value = eval_expression(expression, env);
+            if value.is_error() {
+                return value;
+            }
+            Object::ReturnValue(Box::new(value))
+        }
+        Statement::LET { name, value } => {
+            let value = eval_expression(value, env);
+            if value.is_error() {
+                return value;
+            }
+            if let Expression::Identifier(name, _) = name {
+                env.set(name, value);
+            }
+            Object::Null
+        }
+    }
+}
+
+fn eval_expression(expression: &Expression, env: &mut Environment) -> Object {
+    match expression {
+        Expression::Integer(value) => Object::Integer(*value),
+        Expression::Boolean(value) => Object::Boolean(*value),
+        Expression::StringLiteral(value) => Object::String(value.clone()),
+        Expression::Identifier(name, position) => located(eval_identifier(name, env), position),
+        Expression::Prefix {
+            op,
+            right,
+            position,
+        } => {
+            let right = eval_expression(right, env);
+            if right.is_error() {
+                return right;
+            }
+            located(eval_prefix_expression(op, right), position)
+        }
+        Expression::Infix {
+            left,
+            op,
+            right,
+            position,
+        } => {
+            let left = eval_expression(left, env);
+            if left.is_error() {
+                return left;
+            }
+            let right = eval_expression(right, env);
+            if right.is_error() {
+                return right;
+            }
+            located(eval_infix_expression(op, left, right), position)
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            let condition = eval_expression(condition, env);
+            if condition.is_error() {
+                return condition;
+            }
+            if condition.is_truthy() {
+                eval_block_statement(consequence, env)
+            } else if let Some(alternative) = alternative {
+                eval_block_statement(alternative, env)
+            } else {
+                Object::Null
+            }
+        }
+        Expression::Function { parameters, body } => Object::Function {
+            parameters: parameters.clone(),
+            body: body.clone(),
+            env: env.clone(),
+        },
+        Expression::Call {
+            function,
+            arguments,
+            position,
+        } => {
+            let function = eval_expression(function, env);
+            if function.is_error() {
+                return function;
+            }
+            let arguments = match eval_expressions(arguments, env) {
+                Ok(arguments) => arguments,
+                Err(error) => return error,
+            };
+            located(apply_function(function, arguments), position)
+        }
+        Expression::Array(elements) => match eval_expressions(elements, env) {
+            Ok(elements) => Object::Array(elements),
+            Err(error) => error,
+        },
+        Expression::Hash(pairs, position) => located(eval_hash_literal(pairs, env), position),
+        Expression::Index {
+            left,
+            index,
+            position,
+        } => {
+            let left = eval_expression(left, env);
+            if left.is_error() {
+                return left;
+            }
+            let index = eval_expression(index, env);
+            if index.is_error() {
+                return index;
+            }
+            located(eval_index_expression(left, index), position)
+        }
+    }
+}
+
+/// Places an error at the expression it came from, unless it already has a place, as
+/// errors from inside a function body or an operand do.
+fn located(result: Object, position: &Position) -> Object {
+    match result {
+        Object::Error(message, None) => Object::Error(message, Some(position.0)),
+        result => result,
+    }
+}
+
+fn eval_expressions(
+    expressions: &[Expression],
+    env: &mut Environment,
+) -> Result<Vec<Object>, Object> {
+    let mut result = vec![];
+
+    for expression in expressions {
+        let evaluated = eval_expression(expression, env);
+        if evaluated.is_error() {
+            return Err(evaluated);
+        }
+        result.push(evaluated);
+    }
+    Ok(result)
+}
+
+fn apply_function(function: Object, arguments: Vec<Object>) -> Object {
+    match function {
+        Object::Function {
+            parameters,
+            body,
+            env,
+        } => {
+            if parameters.len() != arguments.len() {
+                return Object::Error(
+                    format!(
+                        "wrong number of arguments: want={}, got={}",
+                        parameters.len(),
+                        arguments.len()
+                    ),
+                    None,
+                );
+            }
+
+            let depth = CALL_DEPTH.get();
+            if depth >= MAX_CALL_DEPTH {
+                return Object::Error("stack overflow".to_string(), None);
+            }
+
+            let mut extended_env = Environment::new_enclosed(&env);
+            for (parameter, argument) in parameters.iter().zip(arguments) {
+                if let Expression::Identifier(name, _) = parameter {
+                    extended_env.set(name, argument);
+                }
+            }
+
+            CALL_DEPTH.set(depth + 1);
+            let evaluated = eval_block_statement(&body, &mut extended_env);
+            CALL_DEPTH.set(depth);
+            match evaluated {
+                Object::ReturnValue(value) => *value,
+                evaluated => evaluated,
+            }
+        }
+        Object::Builtin { function, .. } => function(arguments),
+        x => Object::Error(format!("not a function: {}", x.type_name()), None),
+    }
+}
+
+fn eval_hash_literal(pairs: &[(Expression, Expression)], env: &mut Environment) -> Object {
+    let mut hash = BTreeMap::new();
+
+    for (key, value) in pairs {
+        let key = eval_expression(key, env);
+        if key.is_error() {
+            return key;
+        }
+        let hash_key = match key.hash_key() {
+            Some(hash_key) => hash_key,
+            None => {
+                return Object::Error(format!("unusable as hash key: {}", key.type_name()), None)
+            }
+        };
+        let value = eval_expression(value, env);
+        if value.is_error() {
+            return value;
+        }
+        hash.insert(hash_key, (key, value));
+    }
+    Object::Hash(hash)
+}
+
+/// Indexing outside of an array or a missing hash key yields `null` rather than an error.
+/// Shared with the virtual machine, like the operators below.
+pub fn eval_index_expression(left: Object, index: Object) -> Object {
+    match (left, index) {
+        (Object::Hash(pairs), index) => match index.hash_key() {
+            Some(hash_key) => pairs
+                .get(&hash_key)
+                .map_or(Object::Null, |(_, value)| value.clone()),
+            None => Object::Error(format!("unusable as hash key: {}", index.type_name()), None),
+        },
+        (Object::Array(elements), Object::Integer(index)) => usize::try_from(index)
+            .ok()
+            .and_then(|index| elements.get(index).cloned())
+            .unwrap_or(Object::Null),
+        (left, _) => Object::Error(
+            format!("index operator not supported: {}", left.type_name()),
+            None,
+        ),
+    }
+}
+
+/// Bindings in the environment shadow the builtins.
+fn eval_identifier(name: &str, env: &Environment) -> Object {
+    match env.get(name).or_else(|| builtins::lookup(name)) {
+        Some(value) => value,
+        None => Object::Error(format!("identifier not found: {}", name), None),
+    }
+}
+
+pub fn eval_prefix_expression(op: &str, right: Object) -> Object {
+    match (op, right) {
+        ("!", right) => Object::Boolean(!right.is_truthy()),
+        ("-", Object::Integer(value)) => match value.checked_neg() {
+            Some(value) => Object::Integer(value),
+            None => Object::Error(format!("integer overflow: -{}", value), None),
+        },
+        (op, right) => Object::Error(
+            format!("unknown operator: {}{}", op, right.type_name()),
+            None,
+        ),
+    }
+}
+
+pub fn eval_infix_expression(op: &str, left: Object, right: Object) -> Object {
+    match (left, right) {
+        (Object::Integer(left), Object::Integer(right)) => {
+            eval_integer_infix_expression(op, left, right)
+        }
+        (Object::Boolean(left), Object::Boolean(right)) => match op {
+            "==" => Object::Boolean(left == right),
+            "!=" => Object::Boolean(left != right),
+            _ => Object::Error(format!("unknown operator: BOOLEAN {} BOOLEAN", op), None),
+        },
+        (Object::String(left), Object::String(right)) => match op {
+            "+" => Object::String(left + &right),
+            "==" => Object::Boolean(left == right),
+            "!=" => Object::Boolean(left != right),
+            _ => Object::Error(format!("unknown operator: STRING {} STRING", op), None),
+        },
+        (Object::Null, Object::Null) if op == "==" || op == "!=" => Object::Boolean(op == "=="),
+        (left, right) if left.type_name() != right.type_name() => Object::Error(
+            format!(
+                "type mismatch: {} {} {}",
+                left.type_name(),
+                op,
+                right.type_name()
+            ),
+            None,
+        ),
+        (left, right) => Object::Error(
+            format!(
+                "unknown operator: {} {} {}",
+                left.type_name(),
+                op,
+                right.type_name()
+            ),
+            None,
+        ),
+    }
+}
+
+fn eval_integer_infix_expression(op: &str, left: i64, right: i64) -> Object {
+    let value = match op {
+        "+" => left.checked_add(right),
+        "-" => left.checked_sub(right),
+        "*" => left.checked_mul(right),
+        "/" if right == 0 => return Object::Error("division by zero".to_string(), None),
+        "/" => left.checked_div(right),
+        "<" => return Object::Boolean(left < right),
+        ">" => return Object::Boolean(left > right),
+        "==" => return Object::Boolean(left == right),
+        "!=" => return Object::Boolean(left != right),
+        _ => return Object::Error(format!("unknown operator: INTEGER {} INTEGER", op), None),
+    };
+
+    match value {
+        Some(value) => Object::Integer(value),
+        None => Object::Error(format!("integer overflow: {} {} {}", left, op, right), None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::environment::Environment;
+    use crate::evaluator::eval;
+    use crate::lexer::Lexer;
+    use crate::object::{HashKey, Object};
+    use crate::parser::Parser;
+    use std::thread;
+
+    fn test_eval(input: &str) -> Object {
+        let lexer = Lexer::new(String::from(input));
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        eval(&program, &mut Environment::new())
+    }
+
+    fn test_integer_object(object: Object, expected: i64) {
+        match object {
+            Object::Integer(value) => assert_eq!(value, expected),
+            x => panic!("object is not Integer. got={:?}", x),
+        }
+    }
+
+    fn test_boolean_object(object: Object, expected: bool) {
+        match object {
+            Object::Boolean(value) => assert_eq!(value, expected),
+            x => panic!("object is not Boolean. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_eval_integer_expression() {
+        let tests = [
+            ("5", 5),
+            ("10", 10),
+            ("-5", -5),
+            ("-10", -10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("5 * 2 + 10", 20),
+            ("5 + 2 * 10", 25),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("2 * (5 + 10)", 30),
+            ("3 * 3 * 3 + 10", 37),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expression() {
+        let tests = [
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 < 1", false),
+            ("1 > 1", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 == 2", false),
+            ("1 != 2", true),
+            ("true == true", true),
+            ("false == false", true),
+            ("true == false", false),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("(1 < 2) == false", false),
+            ("(1 > 2) == true", false),
+            ("(1 > 2) == false", true),
+        ];
+
+        for (input, expected) in tests {
+            test_boolean_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_bang_operator() {
+        let tests = [
+            ("!true", false),
+            ("!false", true),
+            ("!5", false),
+            ("!!true", true),
+            ("!!false", false),
+            ("!!5", true),
+        ];
+
+        for (input, expected) in tests {
+            test_boolean_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let tests = [
+            ("if (true) { 10 }", Some(10)),
+            ("if (false) { 10 }", None),
+            ("if (1) { 10 }", Some(10)),
+            ("if (1 < 2) { 10 }", Some(10)),
+            ("if (1 > 2) { 10 }", None),
+            ("if (1 > 2) { 10 } else { 20 }", Some(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Some(10)),
+        ];
+
+        for (input, expected) in tests {
+            match expected {
+                Some(expected) => test_integer_object(test_eval(input), expected),
+                None => assert!(matches!(test_eval(input), Object::Null)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let tests = [
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            (
+                r#"
+                if (10 > 1) {
+                  if (10 > 1) {
+                    return 10;
+                  }
+                  return 1;
+                }
+                "#,
+                10,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let tests = [
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            (
+                "if (10 > 1) { true + false; }",
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            (
+                r#"
+                if (10 > 1) {
+                  if (10 > 1) {
+                    return true + false;
+                  }
+                  return 1;
+                }
+                "#,
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            ("foobar", "identifier not found: foobar"),
+            (
+                "let f = fn() { if (false) { let y = 1; } y }; f()",
+                "identifier not found: y",
+            ),
+            ("1[0]", "index operator not supported: INTEGER"),
+            ("[1][true]", "index operator not supported: ARRAY"),
+            (
+                r#"{"name": "Monkey"}[fn(x) { x }];"#,
+                "unusable as hash key: FUNCTION",
+            ),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("{1: 2}[{}]", "unusable as hash key: HASH"),
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""a" + 1"#, "type mismatch: STRING + INTEGER"),
+            ("5(1)", "not a function: INTEGER"),
+            (
+                "fn(x) { x }(1, 2)",
+                "wrong number of arguments: want=1, got=2",
+            ),
+            ("10 / 0", "division by zero"),
+            (
+                "9223372036854775807 + 1",
+                "integer overflow: 9223372036854775807 + 1",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(message, _) => assert_eq!(message, expected),
+                x => panic!("no error object returned. got={:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_positions() {
+        // The line and column of the token each error is reported at.
+        let tests = [
+            ("1 +\n  true", (1, 3)),
+            ("let x = 1;\nx + y", (2, 5)),
+            ("-true", (1, 1)),
+            ("let f = fn(x) {\n  x * true\n};\nf(1)", (2, 5)),
+            ("len(1)", (1, 4)),
+            ("fn(x) { x }(1, 2)", (1, 12)),
+            ("{[1]: 2}", (1, 1)),
+            ("[1][true]", (1, 4)),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(_, Some(span)) => {
+                    assert_eq!((span.line, span.column), expected, "{}", input)
+                }
+                x => panic!("no located error returned for {}. got={:?}", input, x),
+            }
+        }
+    }
+
+    #[test]
+    fn test_string_literal() {
+        match test_eval(r#""Hello World!""#) {
+            Object::String(value) => assert_eq!(value, "Hello World!"),
+            x => panic!("object is not String. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        match test_eval(r#"let greet = fn(name) { "Hello" + ", " + name + "!" }; greet("World")"#) {
+            Object::String(value) => assert_eq!(value, "Hello, World!"),
+            x => panic!("object is not String. got={:?}", x),
+        }
+        test_boolean_object(test_eval(r#""a" + "b" == "ab""#), true);
+        test_boolean_object(test_eval(r#""a" != "a""#), false);
+    }
+
+    #[test]
+    fn test_array_literals() {
+        match test_eval("[1, 2 * 2, 3 + 3]") {
+            Object::Array(elements) => {
+                assert_eq!(elements.len(), 3);
+                test_integer_object(elements[0].clone(), 1);
+                test_integer_object(elements[1].clone(), 4);
+                test_integer_object(elements[2].clone(), 6);
+            }
+            x => panic!("object is not Array. got={:?}", x),
+        }
+        assert_eq!(
+            test_eval(r#"[1, "two", [true]]"#).to_string(),
+            "[1, two, [true]]"
+        );
+    }
+
+    #[test]
+    fn test_array_index_expressions() {
+        let tests = [
+            ("[1, 2, 3][0]", Some(1)),
+            ("[1, 2, 3][1]", Some(2)),
+            ("[1, 2, 3][2]", Some(3)),
+            ("let i = 0; [1][i];", Some(1)),
+            ("[1, 2, 3][1 + 1];", Some(3)),
+            ("let myArray = [1, 2, 3]; myArray[2];", Some(3)),
+            (
+                "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];",
+                Some(6),
+            ),
+            (
+                "let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]",
+                Some(2),
+            ),
+            ("[1, 2, 3][3]", None),
+            ("[1, 2, 3][-1]", None),
+        ];
+
+        for (input, expected) in tests {
+            match expected {
+                Some(expected) => test_integer_object(test_eval(input), expected),
+                None => assert!(matches!(test_eval(input), Object::Null)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let input = r#"
+        let two = "two";
+        {
+            "one": 10 - 9,
+            two: 1 + 1,
+            "thr" + "ee": 6 / 2,
+            4: 4,
+            true: 5,
+            false: 6
+        }
+        "#;
+
+        match test_eval(input) {
+            Object::Hash(pairs) => {
+                let expected = [
+                    (HashKey::String("one".to_string()), 1),
+                    (HashKey::String("two".to_string()), 2),
+                    (HashKey::String("three".to_string()), 3),
+                    (HashKey::Integer(4), 4),
+                    (HashKey::Boolean(true), 5),
+                    (HashKey::Boolean(false), 6),
+                ];
+                assert_eq!(pairs.len(), expected.len());
+                for (key, value) in expected {
+                    test_integer_object(pairs[&key].1.clone(), value);
+                }
+            }
+            x => panic!("object is not Hash. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let tests = [
+            (r#"{"foo": 5}["foo"]"#, Some(5)),
+            (r#"{"foo": 5}["bar"]"#, None),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Some(5)),
+            (r#"{}["foo"]"#, None),
+            ("{5: 5}[5]", Some(5)),
+            ("{true: 5}[true]", Some(5)),
+            ("{false: 5}[false]", Some(5)),
+            ("{1: 1, 1: 2}[1]", Some(2)),
+        ];
+
+        for (input, expected) in tests {
+            match expected {
+                Some(expected) => test_integer_object(test_eval(input), expected),
+                None => assert!(matches!(test_eval(input), Object::Null)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let tests = [
+            (r#"len("")"#, "0"),
+            (r#"len("four")"#, "4"),
+            (r#"len("hello world")"#, "11"),
+            (r#"len("日本語")"#, "3"),
+            ("len([1, 2, 3])", "3"),
+            ("len([])", "0"),
+            (
+                "len(1)",
+                "ERROR: argument to `len` not supported, got INTEGER",
+            ),
+            (
+                r#"len("one", "two")"#,
+                "ERROR: wrong number of arguments. got=2, want=1",
+            ),
+            ("first([1, 2, 3])", "1"),
+            ("first([])", "null"),
+            (
+                "first(1)",
+                "ERROR: argument to `first` must be ARRAY, got INTEGER",
+            ),
+            ("last([1, 2, 3])", "3"),
+            ("last([])", "null"),
+            (
+                "last(1)",
+                "ERROR: argument to `last` must be ARRAY, got INTEGER",
+            ),
+            ("rest([1, 2, 3])", "[2, 3]"),
+            ("rest([1])", "[]"),
+            ("rest([])", "null"),
+            ("rest()", "ERROR: wrong number of arguments. got=0, want=1"),
+            ("push([], 1)", "[1]"),
+            ("let a = [1]; push(a, 2); a", "[1]"),
+            (
+                "push(1, 1)",
+                "ERROR: argument to `push` must be ARRAY, got INTEGER",
+            ),
+            (r#"puts("hello", "world!")"#, "null"),
+            ("let len = fn(x) { 42 }; len([])", "42"),
+            ("len", "builtin function len"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input).to_string(), expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = [
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_function_object() {
+        match test_eval("fn(x) { x + 2; };") {
+            Object::Function {
+                parameters, body, ..
+            } => {
+                assert_eq!(parameters.len(), 1);
+                assert_eq!(parameters[0].to_string(), "x");
+                assert_eq!(body.to_string(), "{ (x + 2) }");
+            }
+            x => panic!("object is not Function. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_function_application() {
+        let tests = [
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+            (
+                "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(10);",
+                55,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = [
+            (
+                r#"
+                let newAdder = fn(x) { fn(y) { x + y }; };
+                let addTwo = newAdder(2);
+                addTwo(2);
+                "#,
+                4,
+            ),
+            (
+                r#"
+                let add = fn(a, b) { a + b };
+                let partial = fn(f, a) { fn(b) { f(a, b) } };
+                let addTen = partial(add, 10);
+                addTen(5) + addTen(1);
+                "#,
+                26,
+            ),
+            (
+                r#"
+                let later = fn() { value };
+                let value = 7;
+                later();
+                "#,
+                7,
+            ),
+            (
+                "let x = 1; let f = fn() { let y = x; let x = 2; y }; f()",
+                1,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        // Each call takes several native frames, more than a test thread's stack allows.
+        let tests = thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(|| {
+                let countdown = "let f = fn(x) { if (x == 0) { 0 } else { 1 + f(x - 1) } };";
+                [
+                    "let f = fn(x) { f(x) }; f(1)".to_string(),
+                    format!("{} f(1022)", countdown),
+                    format!("{} f(1023)", countdown),
+                ]
+                .map(|input| test_eval(&input).to_string())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(
+            tests,
+            ["ERROR: stack overflow", "1022", "ERROR: stack overflow"]
+        );
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let tests = [
+            ("let x = 1; let f = fn(x) { x }; f(2) + x;", 3),
+            ("let x = 1; let f = fn() { let x = 10; x }; f() + x;", 11),
+            ("let x = 1; let x = x + 1; x;", 2),
+            ("let x = 1; if (true) { let x = 2; } x;", 2),
+            (
+                "let x = 1; let f = fn() { if (true) { let x = 5; } x }; f() + x;",
+                6,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+}
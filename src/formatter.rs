@@ -0,0 +1,676 @@
+use crate::cst::{parse, NodeKind, SyntaxNode, SyntaxToken};
+use crate::parse_error::ParseError;
+use crate::token::{TokenType, TriviaKind};
+use std::collections::HashMap;
+
+const MAX_WIDTH: usize = 80;
+const INDENT: usize = 4;
+
+/// Prints a script in the canonical style, keeping its comments.
+/// Scripts with parse errors are left alone, since their layout cannot be trusted.
+pub fn format(source: &str) -> Result<String, Vec<ParseError>> {
+    let parse = parse(source);
+    if !parse.errors.is_empty() {
+        return Err(parse.errors);
+    }
+
+    let root = parse.syntax();
+    let formatter = Formatter {
+        comments: collect_comments(&root),
+    };
+    let out = print(&formatter.program(&root));
+    let out = out.trim_end();
+    if out.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("{}\n", out))
+}
+
+// The layout is described as a document first and laid out afterwards,
+// so that a group can be measured before deciding whether it has to break.
+
+#[derive(Debug, Clone)]
+enum Doc {
+    Text(String),
+    /// A space, or a newline when the enclosing group breaks.
+    Line,
+    /// Nothing, or a newline when the enclosing group breaks.
+    SoftLine,
+    HardLine,
+    /// Indents the lines that break inside it.
+    Nest(Vec<Doc>),
+    /// Laid out on one line if it fits, otherwise all of its lines break.
+    Group(Vec<Doc>),
+    Concat(Vec<Doc>),
+    /// A line comment, held back until the end of the line so that nothing is printed after it.
+    LineSuffix(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+fn print(doc: &Doc) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    let mut suffixes: Vec<&str> = vec![];
+    let mut stack = vec![(0, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                out.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            Doc::Nest(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (indent + INDENT, mode, doc)))
+            }
+            Doc::Group(docs) => {
+                let width = MAX_WIDTH as isize - column as isize;
+                let mode = if mode == Mode::Flat || fits(width, docs, &stack) {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+            Doc::Line if mode == Mode::Flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                for suffix in suffixes.drain(..) {
+                    out.push(' ');
+                    out.push_str(suffix);
+                }
+                out.truncate(out.trim_end_matches(' ').len());
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                column = indent;
+            }
+            Doc::LineSuffix(comment) => suffixes.push(comment),
+        }
+    }
+    for suffix in suffixes {
+        out.push(' ');
+        out.push_str(suffix);
+    }
+    out
+}
+
+/// Whether `docs` fit on one line in `width` columns, together with whatever follows them
+/// up to the next line break.
+fn fits(mut width: isize, docs: &[Doc], rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack: Vec<(Mode, &Doc)> = docs.iter().rev().map(|doc| (Mode::Flat, doc)).collect();
+    let mut rest = rest.iter().rev();
+    // A line comment pushes everything after it on its line behind the comment,
+    // so a group that continues after one is broken instead.
+    let mut has_suffix = false;
+
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => {
+                width -= text.chars().count() as isize;
+                if width < 0 {
+                    return false;
+                }
+            }
+            Doc::Concat(docs) | Doc::Nest(docs) | Doc::Group(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (mode, doc)))
+            }
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat && has_suffix => return false,
+            Doc::Line if mode == Mode::Flat => width -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine => return true,
+            Doc::HardLine => return mode == Mode::Break,
+            Doc::LineSuffix(_) => has_suffix = true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Comment {
+    text: String,
+    kind: TriviaKind,
+    newline_after: bool,
+    blank_line_after: bool,
+}
+
+#[derive(Debug, Default)]
+struct TokenComments {
+    /// Comments on the lines before the token.
+    leading: Vec<Comment>,
+    /// Comments after the token on the same line.
+    trailing: Vec<Comment>,
+    blank_line_before: bool,
+}
+
+/// Assigns every comment to a token, keyed by where the token starts.
+fn collect_comments(root: &SyntaxNode) -> HashMap<usize, TokenComments> {
+    let tokens = root.descendant_tokens();
+    let mut comments: HashMap<usize, TokenComments> = HashMap::new();
+    let newlines = |text: &str| text.matches('\n').count();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let trivia = token.leading_trivia();
+        let mut split = 0;
+        // What comes before the first newline still belongs to the line of the previous token.
+        if i > 0 {
+            split = trivia
+                .iter()
+                .position(|trivia| {
+                    trivia.kind == TriviaKind::Whitespace && trivia.text.contains('\n')
+                })
+                .unwrap_or(trivia.len());
+            let previous = comments
+                .entry(tokens[i - 1].text_range().start)
+                .or_default();
+            previous.trailing = trivia[..split]
+                .iter()
+                .filter(|trivia| trivia.kind != TriviaKind::Whitespace)
+                .map(|trivia| Comment {
+                    text: trivia.text.trim_end().to_string(),
+                    kind: trivia.kind,
+                    newline_after: false,
+                    blank_line_after: false,
+                })
+                .collect();
+        }
+
+        let rest = &trivia[split..];
+        let entry = comments.entry(token.text_range().start).or_default();
+        entry.blank_line_before = matches!(
+            rest.first(),
+            Some(trivia) if trivia.kind == TriviaKind::Whitespace && newlines(&trivia.text) >= 2
+        );
+        for (j, trivia) in rest.iter().enumerate() {
+            if trivia.kind == TriviaKind::Whitespace {
+                continue;
+            }
+            let after = rest
+                .get(j + 1)
+                .filter(|next| next.kind == TriviaKind::Whitespace)
+                .map(|next| newlines(&next.text))
+                .unwrap_or(0);
+            entry.leading.push(Comment {
+                text: trivia.text.trim_end().to_string(),
+                kind: trivia.kind,
+                newline_after: after >= 1 || trivia.kind == TriviaKind::LineComment,
+                blank_line_after: after >= 2,
+            });
+        }
+    }
+    comments
+}
+
+struct Formatter {
+    comments: HashMap<usize, TokenComments>,
+}
+
+impl Formatter {
+    fn comments_of(&self, token: &SyntaxToken) -> &TokenComments {
+        &self.comments[&token.text_range().start]
+    }
+
+    /// Comments that go on their own lines, each followed by its line break.
+    fn leading(&self, comments: &[Comment]) -> Vec<Doc> {
+        let mut docs = vec![];
+        for comment in comments {
+            docs.push(Doc::Text(comment.text.clone()));
+            if comment.newline_after {
+                docs.push(Doc::HardLine);
+                if comment.blank_line_after {
+                    docs.push(Doc::HardLine);
+                }
+            } else {
+                docs.push(Doc::Text(" ".to_string()));
+            }
+        }
+        docs
+    }
+    fn trailing(&self, comments: &[Comment]) -> Vec<Doc> {
+        comments
+            .iter()
+            .map(|comment| match comment.kind {
+                TriviaKind::LineComment => Doc::LineSuffix(comment.text.clone()),
+                _ => Doc::Text(format!(" {}", comment.text)),
+            })
+            .collect()
+    }
+
+    fn token(&self, token: &SyntaxToken) -> Doc {
+        let mut docs = self.leading(&self.comments_of(token).leading);
+        docs.push(self.token_text(token));
+        Doc::Concat(docs)
+    }
+    /// The token and its trailing comments, for tokens whose leading comments are placed apart.
+    fn token_text(&self, token: &SyntaxToken) -> Doc {
+        let mut docs = vec![Doc::Text(token.text().to_string())];
+        docs.extend(self.trailing(&self.comments_of(token).trailing));
+        Doc::Concat(docs)
+    }
+    /// Keeps the comments of a token that the canonical style leaves out.
+    fn comments_only(&self, token: &SyntaxToken) -> Doc {
+        let comments = self.comments_of(token);
+        let mut docs = self.leading(&comments.leading);
+        docs.extend(self.trailing(&comments.trailing));
+        Doc::Concat(docs)
+    }
+    /// Comments in front of a closing delimiter stay inside the indented body.
+    fn closing_comments(&self, token: &SyntaxToken) -> Vec<Doc> {
+        let comments = &self.comments_of(token).leading;
+        if comments.is_empty() {
+            return vec![];
+        }
+        let mut docs = vec![Doc::HardLine];
+        docs.extend(self.leading(comments));
+        // The line break before the delimiter comes from the enclosing group.
+        while matches!(docs.last(), Some(Doc::HardLine))
+            || matches!(docs.last(), Some(Doc::Text(text)) if text == " ")
+        {
+            docs.pop();
+        }
+        docs
+    }
+
+    fn blank_line_before(&self, node: &SyntaxNode) -> bool {
+        let first = &node.descendant_tokens()[0];
+        self.comments_of(first).blank_line_before
+    }
+
+    fn program(&self, node: &SyntaxNode) -> Doc {
+        let statements = node.child_nodes();
+        let mut docs = self.statements(&statements, false);
+
+        let eof = node
+            .child_tokens()
+            .pop()
+            .expect("the program ends with EOF");
+        let comments = self.comments_of(&eof);
+        if !comments.leading.is_empty() {
+            if !statements.is_empty() {
+                docs.push(Doc::HardLine);
+                if comments.blank_line_before {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            docs.extend(self.leading(&comments.leading));
+        }
+        Doc::Concat(docs)
+    }
+
+    /// One statement per line, keeping single blank lines between them.
+    fn statements(&self, statements: &[SyntaxNode], in_block: bool) -> Vec<Doc> {
+        let mut docs = vec![];
+        for (i, statement) in statements.iter().enumerate() {
+            if i > 0 {
+                docs.push(Doc::HardLine);
+                if self.blank_line_before(statement) {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            let semicolon = needs_semicolon(statement, statements.get(i + 1), in_block);
+            docs.push(self.statement(statement, semicolon));
+        }
+        docs
+    }
+
+    fn statement(&self, node: &SyntaxNode, semicolon: bool) -> Doc {
+        let children = node.child_nodes();
+        let mut docs = match node.kind() {
+            NodeKind::LetStatement => vec![
+                self.token(&find_token(node, TokenType::LET)),
+                Doc::Text(" ".to_string()),
+                self.expression(&children[0]),
+                Doc::Text(" ".to_string()),
+                self.token(&find_token(node, TokenType::ASSIGN)),
+                Doc::Text(" ".to_string()),
+                self.expression(&children[1]),
+            ],
+            NodeKind::ReturnStatement => vec![
+                self.token(&find_token(node, TokenType::RETURN)),
+                Doc::Text(" ".to_string()),
+                self.expression(&children[0]),
+            ],
+            _ => vec![self.expression(&children[0])],
+        };
+
+        let token = node
+            .child_tokens()
+            .into_iter()
+            .find(|token| *token.kind() == TokenType::SEMICOLON);
+        docs.push(match token {
+            Some(token) if semicolon => self.token(&token),
+            Some(token) => self.comments_only(&token),
+            None if semicolon => Doc::Text(";".to_string()),
+            None => Doc::Concat(vec![]),
+        });
+        Doc::Concat(docs)
+    }
+
+    fn block(&self, node: &SyntaxNode) -> Doc {
+        Doc::Group(self.block_docs(node))
+    }
+    /// The block without its group, so that both branches of an `if` can break together.
+    fn block_docs(&self, node: &SyntaxNode) -> Vec<Doc> {
+        let open = find_token(node, TokenType::LBRACE);
+        let close = find_token(node, TokenType::RBRACE);
+        let statements = node.child_nodes();
+        let closing_comments = self.closing_comments(&close);
+        if statements.is_empty() && closing_comments.is_empty() {
+            return vec![self.token(&open), self.token_text(&close)];
+        }
+
+        let mut body = vec![];
+        if !statements.is_empty() {
+            body.push(Doc::Line);
+            body.extend(self.statements(&statements, true));
+        }
+        body.extend(closing_comments);
+        vec![
+            self.token(&open),
+            Doc::Nest(body),
+            Doc::Line,
+            self.token_text(&close),
+        ]
+    }
+
+    /// A delimited, comma separated list: on one line if it fits, otherwise one item per line.
+    fn list(&self, node: &SyntaxNode) -> Doc {
+        let tokens = node.child_tokens();
+        let open = &tokens[0];
+        let close = &tokens[tokens.len() - 1];
+        let commas: Vec<&SyntaxToken> = tokens
+            .iter()
+            .filter(|token| *token.kind() == TokenType::COMMA)
+            .collect();
+        let items = node.child_nodes();
+        let closing_comments = self.closing_comments(close);
+        if items.is_empty() && closing_comments.is_empty() {
+            return Doc::Concat(vec![self.token(open), self.token_text(close)]);
+        }
+
+        let mut body = vec![];
+        for (i, item) in items.iter().enumerate() {
+            body.push(if i == 0 { Doc::SoftLine } else { Doc::Line });
+            body.push(self.expression(item));
+            let is_last = i == items.len() - 1;
+            // Only hash literals accept a trailing comma, and the canonical style drops it.
+            match commas.get(i) {
+                Some(comma) if is_last => body.push(self.comments_only(comma)),
+                Some(comma) => body.push(self.token(comma)),
+                None if is_last => {}
+                None => body.push(Doc::Text(",".to_string())),
+            }
+        }
+        body.extend(closing_comments);
+        Doc::Group(vec![
+            self.token(open),
+            Doc::Nest(body),
+            Doc::SoftLine,
+            self.token_text(close),
+        ])
+    }
+
+    fn expression(&self, node: &SyntaxNode) -> Doc {
+        let children = node.child_nodes();
+        let tokens = node.child_tokens();
+        let space = || Doc::Text(" ".to_string());
+
+        match node.kind() {
+            NodeKind::Identifier | NodeKind::Literal => self.token(&tokens[0]),
+            NodeKind::PrefixExpression => {
+                let mut docs = vec![self.token(&tokens[0])];
+                // `- -x` would read as `--x`.
+                if *tokens[0].kind() == TokenType::MINUS
+                    && children[0].kind() == NodeKind::PrefixExpression
+                    && *children[0].child_tokens()[0].kind() == TokenType::MINUS
+                {
+                    docs.push(space());
+                }
+                docs.push(self.expression(&children[0]));
+                Doc::Concat(docs)
+            }
+            NodeKind::InfixExpression => Doc::Concat(vec![
+                self.expression(&children[0]),
+                space(),
+                self.token(&tokens[0]),
+                space(),
+                self.expression(&children[1]),
+            ]),
+            NodeKind::ParenExpression => Doc::Concat(vec![
+                self.token(&tokens[0]),
+                self.expression(&children[0]),
+                self.token(&tokens[1]),
+            ]),
+            NodeKind::IfExpression => {
+                let mut docs = vec![
+                    self.token(&tokens[0]),
+                    space(),
+                    self.token(&tokens[1]),
+                    self.expression(&children[0]),
+                    self.token(&tokens[2]),
+                    space(),
+                ];
+                docs.extend(self.block_docs(&children[1]));
+                if let Some(branch) = children.get(2) {
+                    docs.push(space());
+                    docs.push(self.token(&branch.child_tokens()[0]));
+                    docs.push(space());
+                    docs.extend(self.block_docs(&branch.child_nodes()[0]));
+                }
+                Doc::Group(docs)
+            }
+            NodeKind::FunctionLiteral => Doc::Concat(vec![
+                self.token(&tokens[0]),
+                self.list(&children[0]),
+                space(),
+                self.block(&children[1]),
+            ]),
+            NodeKind::CallExpression => {
+                Doc::Concat(vec![self.expression(&children[0]), self.list(&children[1])])
+            }
+            NodeKind::ArrayLiteral | NodeKind::HashLiteral => self.list(node),
+            NodeKind::HashPair => Doc::Concat(vec![
+                self.expression(&children[0]),
+                self.token(&tokens[0]),
+                space(),
+                self.expression(&children[1]),
+            ]),
+            NodeKind::IndexExpression => Doc::Concat(vec![
+                self.expression(&children[0]),
+                self.token(&tokens[0]),
+                self.expression(&children[1]),
+                self.token(&tokens[1]),
+            ]),
+            kind => unreachable!("{:?} is not an expression", kind),
+        }
+    }
+}
+
+/// Whether a statement is printed with `;`. The last expression of a block is its value,
+/// which reads better without one, and so do expressions that end in a block, like
+/// `if` and `fn`, unless the next statement would otherwise continue them.
+fn needs_semicolon(statement: &SyntaxNode, next: Option<&SyntaxNode>, in_block: bool) -> bool {
+    if statement.kind() != NodeKind::ExpressionStatement {
+        return true;
+    }
+    let ends_in_block = matches!(
+        statement.child_nodes()[0].kind(),
+        NodeKind::IfExpression | NodeKind::FunctionLiteral
+    );
+    match next {
+        None => !in_block && !ends_in_block,
+        Some(next) => {
+            !ends_in_block
+                || matches!(
+                    next.descendant_tokens()[0].kind(),
+                    TokenType::LPAREN | TokenType::LBRACKET | TokenType::MINUS
+                )
+        }
+    }
+}
+
+fn find_token(node: &SyntaxNode, token_type: TokenType) -> SyntaxToken {
+    node.child_tokens()
+        .into_iter()
+        .find(|token| *token.kind() == token_type)
+        .unwrap_or_else(|| panic!("{:?} has no {:?} token", node, token_type))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ast::strip_positions;
+    use crate::formatter::format;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn check(input: &str, expected: &str) {
+        let formatted = format(input).unwrap();
+        assert_eq!(formatted, expected, "input: {}", input);
+        // The canonical style is a fixed point.
+        assert_eq!(format(&formatted).unwrap(), formatted, "input: {}", input);
+
+        let parse = |source: &str| {
+            Parser::new(Lexer::new(source.to_string()))
+                .parse_program()
+                .unwrap()
+        };
+        assert_eq!(
+            strip_positions(&parse(&formatted)),
+            strip_positions(&parse(input)),
+            "input: {}",
+            input
+        );
+    }
+
+    #[test]
+    fn test_format_statements() {
+        let tests = [
+            ("", ""),
+            ("let   x=5", "let x = 5;\n"),
+            ("return x\n", "return x;\n"),
+            ("1+2*3;-a\n!b", "1 + 2 * 3;\n-a;\n!b;\n"),
+            ("let x = 1;\n\n\n\nlet y = 2;", "let x = 1;\n\nlet y = 2;\n"),
+            ("( 1+2 )*3", "(1 + 2) * 3;\n"),
+            ("a [ 0 ]", "a[0];\n"),
+            ("{ \"a\" :1,\"b\":2, }", "{\"a\": 1, \"b\": 2};\n"),
+            ("[ ]; { }; f( )", "[];\n{};\nf();\n"),
+            ("\"tab\\t\"", "\"tab\\t\";\n"),
+            (
+                "let g = - -1; !-a; -!a; !!a",
+                "let g = - -1;\n!-a;\n-!a;\n!!a;\n",
+            ),
+        ];
+        for (input, expected) in tests {
+            check(input, expected);
+        }
+    }
+
+    #[test]
+    fn test_format_blocks() {
+        let tests = [
+            (
+                "let add = fn(a,b){a+b;};",
+                "let add = fn(a, b) { a + b };\n",
+            ),
+            ("if(x<y){x}else{y};", "if (x < y) { x } else { y }\n"),
+            ("fn(){}", "fn() {}\n"),
+            (
+                "let f = fn(x) { let y = x * 2; return y; };",
+                "let f = fn(x) {\n    let y = x * 2;\n    return y;\n};\n",
+            ),
+            (
+                "fn(x) {\nlet y = x;\n\n\ny }",
+                "fn(x) {\n    let y = x;\n\n    y\n}\n",
+            ),
+        ];
+        for (input, expected) in tests {
+            check(input, expected);
+        }
+    }
+
+    #[test]
+    fn test_format_block_statements() {
+        // Statements that end in a block go without `;`, wherever they are,
+        // unless the next statement would continue them.
+        let tests = [
+            ("if (a) { b }; let c = 1;", "if (a) { b }\nlet c = 1;\n"),
+            (
+                "fn() { if (a) { b } else { c }; d }",
+                "fn() {\n    if (a) { b } else { c }\n    d\n}\n",
+            ),
+            (
+                "fn() { if (a) { b } else { c }; }",
+                "fn() { if (a) { b } else { c } }\n",
+            ),
+            ("if (a) { b }; -1", "if (a) { b };\n-1;\n"),
+            ("if (a) { b }; (1)", "if (a) { b };\n(1);\n"),
+            ("if (a) { b }; [1]", "if (a) { b };\n[1];\n"),
+            ("fn() {}; f()", "fn() {}\nf();\n"),
+        ];
+        for (input, expected) in tests {
+            check(input, expected);
+        }
+    }
+
+    #[test]
+    fn test_format_wrapping() {
+        check(
+            "let result = calculate(first_argument, second_argument, third_argument, fourth_argument);",
+            "let result = calculate(\n    first_argument,\n    second_argument,\n    third_argument,\n    fourth_argument\n);\n",
+        );
+        check(
+            "let person = {\"name\": \"Anna\", \"age\": 28, \"languages\": [\"Monkey\", \"Rust\", \"Go\", \"C\"]};",
+            "let person = {\n    \"name\": \"Anna\",\n    \"age\": 28,\n    \"languages\": [\"Monkey\", \"Rust\", \"Go\", \"C\"]\n};\n",
+        );
+        check(
+            "let map = fn(arr, f) { let iter = fn(arr, accumulated) { if (len(arr) == 0) { accumulated } else { iter(rest(arr), push(accumulated, f(first(arr)))) } }; iter(arr, []) };",
+            "let map = fn(arr, f) {\n    let iter = fn(arr, accumulated) {\n        if (len(arr) == 0) {\n            accumulated\n        } else {\n            iter(rest(arr), push(accumulated, f(first(arr))))\n        }\n    };\n    iter(arr, [])\n};\n",
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let tests = [
+            (
+                "// header\n\n// about x\nlet x = 5; // five\nlet y = /* six */ 6;\n// end\n",
+                "// header\n\n// about x\nlet x = 5; // five\nlet y = /* six */ 6;\n// end\n",
+            ),
+            ("let x = 5 // five\n", "let x = 5; // five\n"),
+            (
+                "let f = fn(x) {\n  // double it\n  x * 2\n  // done\n};",
+                "let f = fn(x) {\n    // double it\n    x * 2\n    // done\n};\n",
+            ),
+            ("f(a, // first\nb)", "f(\n    a, // first\n    b\n);\n"),
+            ("/* only a comment */", "/* only a comment */\n"),
+            (
+                "fn() {\n// nothing yet\n}",
+                "fn() {\n    // nothing yet\n}\n",
+            ),
+        ];
+        for (input, expected) in tests {
+            check(input, expected);
+        }
+    }
+
+    #[test]
+    fn test_format_parse_errors() {
+        let errors = format("let = 5;").unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "expected next token to be IDENT, got ASSIGN instead"
+        );
+    }
+}
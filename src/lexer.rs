@@ -1,52 +1,85 @@
-use crate::token::{lookup_ident, Token, TokenType};
+use crate::parse_error::ParseError;
+use crate::token::{lookup_ident, Span, Token, TokenType, Trivia, TriviaKind};
 
 pub struct Lexer {
     input: String,
     position: usize,
     read_position: usize,
-    ch: u8,
+    ch: char,
+    // The line and column of `ch`.
+    line: usize,
+    column: usize,
+    /// Malformed string literals still produce a token; the problem is recorded here.
+    pub errors: Vec<ParseError>,
+    /// Whether whitespace and comments are attached to the tokens as trivia.
+    retain_trivia: bool,
 }
 
 impl Lexer {
     pub fn new(input: String) -> Lexer {
         let mut lexer = Lexer {
-            input: input,
+            input,
             position: 0,
             read_position: 0,
-            ch: 0,
+            ch: '\0',
+            line: 1,
+            column: 0,
+            errors: vec![],
+            retain_trivia: false,
         };
 
         lexer.read_char();
-        return lexer;
+        lexer
     }
 
-    fn peek_char(&self) -> u8 {
-        if self.read_position >= self.input.len() {
-            0
-        } else {
-            self.input.as_bytes()[self.read_position]
-        }
+    /// Like `new`, but every token carries the whitespace and comments in front of it.
+    pub fn with_trivia(input: String) -> Lexer {
+        let mut lexer = Lexer::new(input);
+        lexer.retain_trivia = true;
+        lexer
     }
+
+    /// The text being tokenized.
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    fn peek_char(&self) -> char {
+        self.input[self.read_position.min(self.input.len())..]
+            .chars()
+            .next()
+            .unwrap_or('\0')
+    }
+    /// `position` and `read_position` are byte offsets, `ch` is the whole character at `position`.
     fn read_char(&mut self) {
-        self.ch = if self.read_position >= self.input.len() {
-            0
-        } else {
-            self.input.as_bytes()[self.read_position]
-        };
+        let previous = self.ch;
+        self.ch = self.peek_char();
         self.position = self.read_position;
-        self.read_position += 1;
+        // Past the end of input `ch` is '\0', which still moves the offsets by one.
+        self.read_position += self.ch.len_utf8();
+
+        if previous == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
     }
 
     fn read_identifier(&mut self) -> &str {
         let position = self.position;
-        while Self::is_letter(&self.ch) {
+        while Self::is_identifier_char(&self.ch) {
             self.read_char();
         }
-        return self.input[position..self.position].as_ref();
+        self.input[position..self.position].as_ref()
     }
 
-    fn is_letter(&ch: &u8) -> bool {
-        return b'a' <= ch && ch <= b'z' || b'A' <= ch && ch <= b'Z' || ch == b'_';
+    /// Identifiers follow Unicode's XID_Start and XID_Continue, plus the underscore.
+    fn is_letter(&ch: &char) -> bool {
+        unicode_ident::is_xid_start(ch) || ch == '_'
+    }
+    fn is_identifier_char(&ch: &char) -> bool {
+        unicode_ident::is_xid_continue(ch)
     }
     fn read_number(&mut self) -> &str {
         let position = self.position;
@@ -54,64 +87,238 @@ impl Lexer {
             self.read_char();
         }
 
-        return self.input[position..self.position].as_ref();
+        self.input[position..self.position].as_ref()
     }
 
-    fn is_digit(&ch: &u8) -> bool {
-        return b'0' <= ch && ch <= b'9';
+    fn is_digit(&ch: &char) -> bool {
+        ch.is_ascii_digit()
     }
 
-    fn skip_whitespace(&mut self) {
-        while self.ch == b' ' || self.ch == b'\t' || self.ch == b'\n' || self.ch == b'\r' {
-            self.read_char()
+    /// Reads a string literal starting at the opening quote and leaves `ch` at the closing one.
+    fn read_string(&mut self) -> String {
+        let (start, line, column) = (self.position, self.line, self.column);
+        let mut value = String::new();
+
+        self.read_char();
+        loop {
+            match self.ch {
+                '"' => break,
+                '\0' => {
+                    self.errors.push(ParseError::UnterminatedString {
+                        span: Span {
+                            start,
+                            end: self.input.len(),
+                            line,
+                            column,
+                        },
+                    });
+                    break;
+                }
+                '\\' => self.read_escape(&mut value),
+                ch => value.push(ch),
+            }
+            self.read_char();
         }
+        value
+    }
+
+    /// Reads an escape sequence starting at the backslash and leaves `ch` at its last character.
+    fn read_escape(&mut self, value: &mut String) {
+        let (start, line, column) = (self.position, self.line, self.column);
+
+        self.read_char();
+        let escaped = match self.ch {
+            'u' => self.read_unicode_escape(),
+            // Leave the end of input to `read_string`, which reports the unterminated string.
+            '\0' => return,
+            ch => escaped_char(ch),
+        };
+
+        match escaped {
+            Some(ch) => value.push(ch),
+            None => {
+                let end = self.position + self.ch.len_utf8();
+                self.errors.push(ParseError::InvalidEscape {
+                    sequence: self.input[start..end].to_string(),
+                    span: Span {
+                        start,
+                        end,
+                        line,
+                        column,
+                    },
+                });
+            }
+        }
+    }
+
+    /// Reads the `{XXXX}` part of a `\u{XXXX}` escape, at most six hex digits.
+    fn read_unicode_escape(&mut self) -> Option<char> {
+        if self.peek_char() != '{' {
+            return None;
+        }
+        self.read_char();
+
+        let mut digits = String::new();
+        while self.peek_char().is_ascii_hexdigit() && digits.len() < 6 {
+            self.read_char();
+            digits.push(self.ch);
+        }
+        if digits.is_empty() || self.peek_char() != '}' {
+            return None;
+        }
+        self.read_char();
+
+        unicode_char(&digits)
     }
-    fn new_token(token_type: TokenType, ch: u8) -> Token {
+
+    /// Skips whitespace, `// line` comments and nestable `/* block */` comments.
+    fn read_trivia(&mut self) -> Vec<Trivia> {
+        let mut trivia = vec![];
+
+        loop {
+            let (start, line, column) = (self.position, self.line, self.column);
+            let kind = if self.ch.is_whitespace() {
+                while self.ch.is_whitespace() {
+                    self.read_char();
+                }
+                TriviaKind::Whitespace
+            } else if self.ch == '/' && self.peek_char() == '/' {
+                while self.ch != '\n' && self.ch != '\0' {
+                    self.read_char();
+                }
+                TriviaKind::LineComment
+            } else if self.ch == '/' && self.peek_char() == '*' {
+                self.skip_block_comment();
+                TriviaKind::BlockComment
+            } else {
+                break;
+            };
+
+            if self.retain_trivia {
+                let end = self.position.min(self.input.len());
+                trivia.push(Trivia {
+                    kind,
+                    text: self.input[start..end].to_string(),
+                    span: Span {
+                        start,
+                        end,
+                        line,
+                        column,
+                    },
+                });
+            }
+        }
+        trivia
+    }
+
+    /// Skips a block comment starting at its `/*` and leaves `ch` after the matching `*/`.
+    fn skip_block_comment(&mut self) {
+        let (start, line, column) = (self.position, self.line, self.column);
+        let mut depth = 0;
+
+        loop {
+            if self.ch == '/' && self.peek_char() == '*' {
+                depth += 1;
+                self.read_char();
+            } else if self.ch == '*' && self.peek_char() == '/' {
+                depth -= 1;
+                self.read_char();
+            } else if self.ch == '\0' {
+                self.errors.push(ParseError::UnterminatedComment {
+                    span: Span {
+                        start,
+                        end: self.input.len(),
+                        line,
+                        column,
+                    },
+                });
+                return;
+            }
+            self.read_char();
+
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+    fn new_token(token_type: TokenType, ch: char) -> Token {
         Token {
             token_type,
-            literal: String::from_utf8(vec![ch]).unwrap(),
+            literal: ch.to_string(),
+            span: Span::default(),
+            leading_trivia: vec![],
         }
     }
 
     pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+        let leading_trivia = self.read_trivia();
 
+        let start = self.position.min(self.input.len());
+        let (line, column) = (self.line, self.column);
+        let mut token = self.read_token();
+        token.span = Span {
+            start,
+            end: self.position.min(self.input.len()),
+            line,
+            column,
+        };
+        token.leading_trivia = leading_trivia;
+        token
+    }
+
+    /// Reads the token at `ch` and leaves `ch` at the first character after it.
+    fn read_token(&mut self) -> Token {
         let token: Token = match self.ch {
-            b'=' if self.peek_char() == b'=' => Token {
+            '=' if self.peek_char() == '=' => Token {
                 token_type: TokenType::EQ,
-                literal: (|| -> String {
-                    let mut ch = String::from_utf8(vec![self.ch]).unwrap();
+                literal: {
+                    let mut ch = self.ch.to_string();
                     self.read_char();
-                    ch.push_str(String::from_utf8(vec![self.ch]).unwrap().as_str());
-                    return ch;
-                })(),
+                    ch.push(self.ch);
+                    ch
+                },
+                span: Span::default(),
+                leading_trivia: vec![],
             },
-            b'=' if self.peek_char() != b'=' => Self::new_token(TokenType::ASSIGN, self.ch),
-            b'(' => Self::new_token(TokenType::LPAREN, self.ch),
-            b')' => Self::new_token(TokenType::RPAREN, self.ch),
-            b',' => Self::new_token(TokenType::COMMA, self.ch),
-            b'+' => Self::new_token(TokenType::PLUS, self.ch),
-            b'-' => Self::new_token(TokenType::MINUS, self.ch),
-            b'!' if self.peek_char() == b'=' => Token {
+            '=' if self.peek_char() != '=' => Self::new_token(TokenType::ASSIGN, self.ch),
+            '(' => Self::new_token(TokenType::LPAREN, self.ch),
+            ')' => Self::new_token(TokenType::RPAREN, self.ch),
+            ',' => Self::new_token(TokenType::COMMA, self.ch),
+            '+' => Self::new_token(TokenType::PLUS, self.ch),
+            '-' => Self::new_token(TokenType::MINUS, self.ch),
+            '!' if self.peek_char() == '=' => Token {
                 token_type: TokenType::NotEq,
-                literal: (|| -> String {
-                    let mut ch = String::from_utf8(vec![self.ch]).unwrap();
+                literal: {
+                    let mut ch = self.ch.to_string();
                     self.read_char();
-                    ch.push_str(String::from_utf8(vec![self.ch]).unwrap().as_str());
-                    return ch;
-                })(),
+                    ch.push(self.ch);
+                    ch
+                },
+                span: Span::default(),
+                leading_trivia: vec![],
+            },
+            '!' if self.peek_char() != '=' => Self::new_token(TokenType::BANG, self.ch),
+            '/' => Self::new_token(TokenType::SLASH, self.ch),
+            '*' => Self::new_token(TokenType::ASTERISK, self.ch),
+            '<' => Self::new_token(TokenType::LT, self.ch),
+            '>' => Self::new_token(TokenType::GT, self.ch),
+            ';' => Self::new_token(TokenType::SEMICOLON, self.ch),
+            ':' => Self::new_token(TokenType::COLON, self.ch),
+            '{' => Self::new_token(TokenType::LBRACE, self.ch),
+            '}' => Self::new_token(TokenType::RBRACE, self.ch),
+            '[' => Self::new_token(TokenType::LBRACKET, self.ch),
+            ']' => Self::new_token(TokenType::RBRACKET, self.ch),
+            '"' => Token {
+                token_type: TokenType::STRING,
+                literal: self.read_string(),
+                span: Span::default(),
+                leading_trivia: vec![],
             },
-            b'!' if self.peek_char() != b'=' => Self::new_token(TokenType::BANG, self.ch),
-            b'/' => Self::new_token(TokenType::SLASH, self.ch),
-            b'*' => Self::new_token(TokenType::ASTERISK, self.ch),
-            b'<' => Self::new_token(TokenType::LT, self.ch),
-            b'>' => Self::new_token(TokenType::GT, self.ch),
-            b';' => Self::new_token(TokenType::SEMICOLON, self.ch),
-            b'{' => Self::new_token(TokenType::LBRACE, self.ch),
-            b'}' => Self::new_token(TokenType::RBRACE, self.ch),
-            0 => Token {
+            '\0' => Token {
                 token_type: TokenType::EOF,
                 literal: "".to_string(),
+                span: Span::default(),
+                leading_trivia: vec![],
             },
             _ => {
                 if Self::is_letter(&self.ch) {
@@ -120,48 +327,101 @@ impl Lexer {
                     return Token {
                         token_type: lookup_ident(&literal),
                         literal,
+                        span: Span::default(),
+                        leading_trivia: vec![],
                     };
                 } else if Self::is_digit(&self.ch) {
                     return Token {
                         token_type: TokenType::INT,
                         literal: self.read_number().to_string(),
+                        span: Span::default(),
+                        leading_trivia: vec![],
                     };
                 } else {
                     Token {
                         token_type: TokenType::ILLEGAL,
                         literal: self.ch.to_string(),
+                        span: Span::default(),
+                        leading_trivia: vec![],
                     }
                 }
             }
         };
 
         self.read_char();
-        return token;
+        token
     }
 }
 
+/// The value of a string literal the lexer accepted, decoded from its quoted source text.
+pub fn string_value(text: &str) -> String {
+    let mut value = String::new();
+    let mut chars = text[1..text.len() - 1].chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            value.push(ch);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('u') => {
+                let digits: String = chars.by_ref().skip(1).take_while(|ch| *ch != '}').collect();
+                unicode_char(&digits)
+            }
+            Some(ch) => escaped_char(ch),
+            None => None,
+        };
+        value.extend(escaped);
+    }
+    value
+}
+
+/// The character `\` followed by `ch` stands for, other than a `\u{...}` escape.
+fn escaped_char(ch: char) -> Option<char> {
+    match ch {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        '"' => Some('"'),
+        '\\' => Some('\\'),
+        _ => None,
+    }
+}
+
+/// The character of the hex digits of a `\u{XXXX}` escape.
+fn unicode_char(digits: &str) -> Option<char> {
+    u32::from_str_radix(digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+}
+
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_is_letter() {
-    assert_eq!(Lexer::is_letter(&b'a'), true);
-    assert_eq!(Lexer::is_letter(&b'z'), true);
-    assert_eq!(Lexer::is_letter(&b'A'), true);
-    assert_eq!(Lexer::is_letter(&b'Z'), true);
-    assert_eq!(Lexer::is_letter(&b'_'), true);
-    assert_eq!(Lexer::is_letter(&b' '), false);
+    assert_eq!(Lexer::is_letter(&'a'), true);
+    assert_eq!(Lexer::is_letter(&'z'), true);
+    assert_eq!(Lexer::is_letter(&'A'), true);
+    assert_eq!(Lexer::is_letter(&'Z'), true);
+    assert_eq!(Lexer::is_letter(&'_'), true);
+    assert_eq!(Lexer::is_letter(&'é'), true);
+    assert_eq!(Lexer::is_letter(&'名'), true);
+    assert_eq!(Lexer::is_letter(&'1'), false);
+    assert_eq!(Lexer::is_letter(&'→'), false);
+    assert_eq!(Lexer::is_letter(&' '), false);
 }
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_is_digit() {
-    assert_eq!(Lexer::is_digit(&b'0'), true);
-    assert_eq!(Lexer::is_digit(&b'1'), true);
-    assert_eq!(Lexer::is_digit(&b'2'), true);
-    assert_eq!(Lexer::is_digit(&b'3'), true);
-    assert_eq!(Lexer::is_digit(&b'4'), true);
-    assert_eq!(Lexer::is_digit(&b'5'), true);
-    assert_eq!(Lexer::is_digit(&b'6'), true);
-    assert_eq!(Lexer::is_digit(&b'7'), true);
-    assert_eq!(Lexer::is_digit(&b'8'), true);
-    assert_eq!(Lexer::is_digit(&b'9'), true);
-    assert_eq!(Lexer::is_digit(&b' '), false);
+    assert_eq!(Lexer::is_digit(&'0'), true);
+    assert_eq!(Lexer::is_digit(&'1'), true);
+    assert_eq!(Lexer::is_digit(&'2'), true);
+    assert_eq!(Lexer::is_digit(&'3'), true);
+    assert_eq!(Lexer::is_digit(&'4'), true);
+    assert_eq!(Lexer::is_digit(&'5'), true);
+    assert_eq!(Lexer::is_digit(&'6'), true);
+    assert_eq!(Lexer::is_digit(&'7'), true);
+    assert_eq!(Lexer::is_digit(&'8'), true);
+    assert_eq!(Lexer::is_digit(&'9'), true);
+    assert_eq!(Lexer::is_digit(&' '), false);
 }
 
 #[test]
@@ -173,7 +433,7 @@ let add = fn(x,y) {
   x + y;
 };
 let result = add(five, ten);
-!-/*5;
+!-/ *5;
 5 < 10 > 5;
 
 if (5 < 10) {
@@ -184,6 +444,8 @@ if (5 < 10) {
 
 10 == 10;
 10 != 9;
+[1, 2];
+{"foo": "bar"}
     "#;
 
     let _tests: Vec<(TokenType, String)> = vec![
@@ -260,23 +522,284 @@ if (5 < 10) {
         (TokenType::NotEq, String::from("!=")),
         (TokenType::INT, String::from("9")),
         (TokenType::SEMICOLON, String::from(";")),
+        (TokenType::LBRACKET, String::from("[")),
+        (TokenType::INT, String::from("1")),
+        (TokenType::COMMA, String::from(",")),
+        (TokenType::INT, String::from("2")),
+        (TokenType::RBRACKET, String::from("]")),
+        (TokenType::SEMICOLON, String::from(";")),
+        (TokenType::LBRACE, String::from("{")),
+        (TokenType::STRING, String::from("foo")),
+        (TokenType::COLON, String::from(":")),
+        (TokenType::STRING, String::from("bar")),
+        (TokenType::RBRACE, String::from("}")),
         (TokenType::EOF, String::from("")),
     ];
 
     let mut lexer = Lexer::new(_input.to_string());
     for (i, tt) in _tests.iter().enumerate() {
         let token = lexer.next_token();
+        assert_eq!(token.token_type, tt.0, "tests[{}] - tokentype wrong.", i);
+        assert_eq!(token.literal, tt.1, "tests[{}] - literal wrong.", i);
+    }
+}
+
+#[test]
+fn test_token_spans() {
+    let input = "let five = 5;\nlet ü = five == 10;\n";
+    let tests = [
+        ("let", 0, 3, 1, 1),
+        ("five", 4, 8, 1, 5),
+        ("=", 9, 10, 1, 10),
+        ("5", 11, 12, 1, 12),
+        (";", 12, 13, 1, 13),
+        ("let", 14, 17, 2, 1),
+    ];
+
+    let mut lexer = Lexer::new(input.to_string());
+    for (i, (literal, start, end, line, column)) in tests.iter().enumerate() {
+        let token = lexer.next_token();
+        assert_eq!(token.literal, *literal, "tests[{}] - literal wrong.", i);
         assert_eq!(
-            token.token_type,
-            tt.0,
-            "{}",
-            format!("tests[{}] - tokentype wrong.", i)
+            token.span,
+            Span {
+                start: *start,
+                end: *end,
+                line: *line,
+                column: *column
+            },
+            "tests[{}] - span wrong.",
+            i
         );
+    }
+
+    // `ü` takes two bytes but a single column.
+    let mut token = lexer.next_token();
+    while token.literal != "=" {
+        token = lexer.next_token();
+    }
+    assert_eq!(token.span.line, 2);
+    assert_eq!(token.span.column, 7);
+    assert_eq!(token.span.start, 21);
+
+    let token = lexer.next_token();
+    assert_eq!(token.literal, "five");
+    assert_eq!(token.span.to_string(), "2:9");
+
+    for _ in 0..3 {
+        lexer.next_token();
+    }
+    let token = lexer.next_token();
+    assert_eq!(token.token_type, TokenType::EOF);
+    assert_eq!(token.span.start, input.len());
+    assert_eq!(token.span.end, input.len());
+}
+
+#[test]
+fn test_string_tokens() {
+    let input = r#""foobar" "foo bar" "a\nb\t\"c\"\\" "\u{1F600}\u{e9}" """#;
+    let tests = [
+        (TokenType::STRING, "foobar"),
+        (TokenType::STRING, "foo bar"),
+        (TokenType::STRING, "a\nb\t\"c\"\\"),
+        (TokenType::STRING, "\u{1F600}\u{e9}"),
+        (TokenType::STRING, ""),
+        (TokenType::EOF, ""),
+    ];
+
+    let mut lexer = Lexer::new(input.to_string());
+    for (i, (token_type, literal)) in tests.iter().enumerate() {
+        let token = lexer.next_token();
         assert_eq!(
+            token.token_type, *token_type,
+            "tests[{}] - tokentype wrong.",
+            i
+        );
+        assert_eq!(token.literal, *literal, "tests[{}] - literal wrong.", i);
+        if token.token_type == TokenType::STRING {
+            let text = &input[token.span.start..token.span.end];
+            assert_eq!(string_value(text), *literal, "tests[{}] - value wrong.", i);
+        }
+    }
+    assert!(lexer.errors.is_empty());
+}
+
+#[test]
+fn test_string_errors() {
+    let mut lexer = Lexer::new(r#"let s = "a\qb\u{zz}c"; "open"#.to_string());
+    let tests = [
+        (TokenType::LET, "let"),
+        (TokenType::IDENT, "s"),
+        (TokenType::ASSIGN, "="),
+        (TokenType::STRING, "abzz}c"),
+        (TokenType::SEMICOLON, ";"),
+        (TokenType::STRING, "open"),
+        (TokenType::EOF, ""),
+    ];
+    for (i, (token_type, literal)) in tests.iter().enumerate() {
+        let token = lexer.next_token();
+        assert_eq!(
+            token.token_type, *token_type,
+            "tests[{}] - tokentype wrong.",
+            i
+        );
+        assert_eq!(token.literal, *literal, "tests[{}] - literal wrong.", i);
+    }
+
+    assert_eq!(
+        lexer.errors,
+        vec![
+            ParseError::InvalidEscape {
+                sequence: "\\q".to_string(),
+                span: Span {
+                    start: 10,
+                    end: 12,
+                    line: 1,
+                    column: 11
+                },
+            },
+            ParseError::InvalidEscape {
+                sequence: "\\u{".to_string(),
+                span: Span {
+                    start: 13,
+                    end: 16,
+                    line: 1,
+                    column: 14
+                },
+            },
+            ParseError::UnterminatedString {
+                span: Span {
+                    start: 23,
+                    end: 28,
+                    line: 1,
+                    column: 24
+                },
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_unicode_tokens() {
+    let input = "let π = \"κύκλος 🐵\"; let 名前2 = π → 1;";
+    let tests = [
+        (TokenType::LET, "let"),
+        (TokenType::IDENT, "π"),
+        (TokenType::ASSIGN, "="),
+        (TokenType::STRING, "κύκλος 🐵"),
+        (TokenType::SEMICOLON, ";"),
+        (TokenType::LET, "let"),
+        (TokenType::IDENT, "名前2"),
+        (TokenType::ASSIGN, "="),
+        (TokenType::IDENT, "π"),
+        (TokenType::ILLEGAL, "→"),
+        (TokenType::INT, "1"),
+        (TokenType::SEMICOLON, ";"),
+        (TokenType::EOF, ""),
+    ];
+
+    let mut lexer = Lexer::new(input.to_string());
+    for (i, (token_type, literal)) in tests.iter().enumerate() {
+        let token = lexer.next_token();
+        assert_eq!(
+            token.token_type, *token_type,
+            "tests[{}] - tokentype wrong.",
+            i
+        );
+        assert_eq!(token.literal, *literal, "tests[{}] - literal wrong.", i);
+        assert_eq!(
+            input[token.span.start..token.span.end].trim_matches('"'),
             token.literal,
-            tt.1,
-            "{}",
-            format!("tests[{}] - literal wrong.", i)
+            "tests[{}] - span wrong.",
+            i
+        );
+    }
+}
+
+#[test]
+fn test_comments() {
+    let input = r#"// a line comment
+let x = 1; // trailing
+/* block /* nested */ still comment */ x / 2;
+/* 日本語 */ x"#;
+    let tests = [
+        (TokenType::LET, "let"),
+        (TokenType::IDENT, "x"),
+        (TokenType::ASSIGN, "="),
+        (TokenType::INT, "1"),
+        (TokenType::SEMICOLON, ";"),
+        (TokenType::IDENT, "x"),
+        (TokenType::SLASH, "/"),
+        (TokenType::INT, "2"),
+        (TokenType::SEMICOLON, ";"),
+        (TokenType::IDENT, "x"),
+        (TokenType::EOF, ""),
+    ];
+
+    let mut lexer = Lexer::new(input.to_string());
+    for (i, (token_type, literal)) in tests.iter().enumerate() {
+        let token = lexer.next_token();
+        assert_eq!(
+            token.token_type, *token_type,
+            "tests[{}] - tokentype wrong.",
+            i
         );
+        assert_eq!(token.literal, *literal, "tests[{}] - literal wrong.", i);
+        assert!(token.leading_trivia.is_empty());
+    }
+    assert!(lexer.errors.is_empty());
+}
+
+#[test]
+fn test_unterminated_block_comment() {
+    let mut lexer = Lexer::new("1 /* open /* nested */".to_string());
+    assert_eq!(lexer.next_token().token_type, TokenType::INT);
+    assert_eq!(lexer.next_token().token_type, TokenType::EOF);
+    assert_eq!(
+        lexer.errors,
+        vec![ParseError::UnterminatedComment {
+            span: Span {
+                start: 2,
+                end: 22,
+                line: 1,
+                column: 3
+            }
+        }]
+    );
+}
+
+#[test]
+fn test_trivia() {
+    let input = "// doc\nlet x = /* one */ 1;\n";
+    let mut lexer = Lexer::with_trivia(input.to_string());
+
+    let token = lexer.next_token();
+    assert_eq!(token.token_type, TokenType::LET);
+    let trivia: Vec<(TriviaKind, &str)> = token
+        .leading_trivia
+        .iter()
+        .map(|trivia| (trivia.kind, trivia.text.as_str()))
+        .collect();
+    assert_eq!(
+        trivia,
+        vec![
+            (TriviaKind::LineComment, "// doc"),
+            (TriviaKind::Whitespace, "\n")
+        ]
+    );
+    assert_eq!(token.leading_trivia[1].span.to_string(), "1:7");
+
+    let mut source = String::new();
+    let mut token = token;
+    loop {
+        for trivia in &token.leading_trivia {
+            source.push_str(&trivia.text);
+        }
+        source.push_str(&input[token.span.start..token.span.end]);
+        if token.token_type == TokenType::EOF {
+            break;
+        }
+        token = lexer.next_token();
     }
+    assert_eq!(source, input);
 }
@@ -1,14 +1,232 @@
 use std::io::stdin;
+use std::{env, fs, process, thread};
+
+use crate::ast::Program;
+use crate::compiler::Compiler;
+use crate::environment::Environment;
+use crate::evaluator::eval;
+use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::parse_error::ParseError;
+use crate::parser::Parser;
+use crate::token::TokenType;
+use crate::vm::VM;
 
 mod ast;
+mod builtins;
+mod code;
+mod compiler;
+#[cfg(test)]
+mod conformance;
+mod cst;
+mod diagnostic;
+mod disassembler;
+mod dot;
+mod environment;
+mod evaluator;
+mod formatter;
 mod lexer;
+mod object;
+mod parse_error;
 mod parser;
 mod repl;
+mod serialize;
+mod symbol_table;
 mod token;
+mod vm;
+
+const USAGE: &str = "usage: monkey [[--vm] [<file>] | --emit=tokens|ast|dot <file> | disasm <file> | fmt [--check] <file>...]";
+
+/// What runs scripts and REPL input: the tree-walking evaluator, or the compiler and
+/// the virtual machine with `--vm`.
+#[derive(Debug, Clone, Copy)]
+enum Engine {
+    Evaluator,
+    VM,
+}
+
+/// The evaluator recurses on the native stack, several kilobytes per Monkey call in
+/// debug builds, and allows about a thousand nested calls.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
 fn main() {
+    let cli = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)
+        .expect("failed to spawn the interpreter thread");
+    if cli.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn cli() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [] => repl(Engine::Evaluator),
+        [option] if option == "--vm" => repl(Engine::VM),
+        [option, path] if option == "--vm" && !path.starts_with('-') => run(path, Engine::VM),
+        [option, path] if option.starts_with("--emit=") => emit(&option["--emit=".len()..], path),
+        [command, path] if command == "disasm" => disasm(path),
+        [command, args @ ..] if command == "fmt" => fmt(args),
+        [path] if !path.starts_with('-') => run(path, Engine::Evaluator),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn repl(engine: Engine) {
     println!("Hello! This is the Monkey programming language!");
     println!("Feel free to type in commands");
 
-    repl::start(stdin());
+    repl::start(stdin(), engine);
+}
+
+/// Runs a script, reporting parse and runtime errors on stderr with a non-zero exit status.
+fn run(path: &str, engine: Engine) {
+    let source = read_source(path);
+    let program = parse(path, &source);
+
+    let result = match engine {
+        Engine::Evaluator => eval(&program, &mut Environment::new()),
+        Engine::VM => execute(&program),
+    };
+    if let Object::Error(message, span) = result {
+        match span {
+            Some(span) => eprintln!(
+                "{}",
+                diagnostic::render_in_file(&message, span, path, &source)
+            ),
+            // Compile errors, and the VM running out of stack outside of a call, have no position.
+            None => eprintln!("error: {}\n --> {}", message, path),
+        }
+        process::exit(1);
+    }
+}
+
+/// Compiles and runs a program on the virtual machine, turning compile and runtime
+/// errors into an error object like the evaluator's, placed where the VM stopped.
+fn execute(program: &Program) -> Object {
+    let mut compiler = Compiler::new();
+    if let Err(message) = compiler.compile(program) {
+        return Object::Error(message, None);
+    }
+    let mut vm = VM::new(compiler.bytecode());
+    match vm.run() {
+        Ok(()) => Object::Null,
+        Err(message) => Object::Error(message, vm.position()),
+    }
+}
+
+/// Prints the tokens or the AST of a script as JSON, or the AST as a Graphviz graph.
+fn emit(kind: &str, path: &str) {
+    let source = read_source(path);
+
+    match kind {
+        "tokens" => {
+            let mut lexer = Lexer::new(source.clone());
+            let mut tokens = vec![];
+            loop {
+                let token = lexer.next_token();
+                let is_eof = token.token_type == TokenType::EOF;
+                tokens.push(token);
+                if is_eof {
+                    break;
+                }
+            }
+            let json = serialize::tokens_to_json(&tokens);
+            println!("{}", serde_json::to_string_pretty(&json).unwrap());
+            if !lexer.errors.is_empty() {
+                print_parse_errors(path, &source, &lexer.errors);
+                process::exit(1);
+            }
+        }
+        "ast" => {
+            let json = serialize::program_to_json(&cst::parse(&source)).unwrap_or_else(|errors| {
+                print_parse_errors(path, &source, &errors);
+                process::exit(1);
+            });
+            println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        }
+        "dot" => print!("{}", dot::program_to_dot(&parse(path, &source))),
+        _ => {
+            eprintln!(
+                "error: unknown --emit kind {}, expected tokens, ast or dot",
+                kind
+            );
+            process::exit(2);
+        }
+    }
+}
+
+/// Prints the bytecode the compiler emits for a script.
+fn disasm(path: &str) {
+    let source = read_source(path);
+    let program = parse(path, &source);
+
+    let mut compiler = Compiler::new();
+    if let Err(message) = compiler.compile(&program) {
+        eprintln!("error: {}\n --> {}", message, path);
+        process::exit(1);
+    }
+    print!("{}", disassembler::disassemble(&compiler.bytecode()));
+}
+
+/// Rewrites scripts in the canonical style. With `--check`, only lists the ones that differ.
+fn fmt(args: &[String]) {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let source = read_source(path);
+        let formatted = match formatter::format(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                print_parse_errors(path, &source, &errors);
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", path);
+            failed = true;
+        } else if let Err(error) = fs::write(path, formatted) {
+            eprintln!("error: could not write {}: {}", path, error);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn read_source(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("error: could not read {}: {}", path, error);
+        process::exit(1);
+    })
+}
+
+fn parse(path: &str, source: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(source.to_string()));
+    parser.parse_program().unwrap_or_else(|errors| {
+        print_parse_errors(path, source, &errors);
+        process::exit(1);
+    })
+}
+
+fn print_parse_errors(path: &str, source: &str, errors: &[ParseError]) {
+    for error in errors {
+        eprintln!("{}", error.render_in_file(path, source));
+    }
 }
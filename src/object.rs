@@ -0,0 +1,153 @@
+use crate::ast::{BlockStatement, Expression};
+use crate::code::Instructions;
+use crate::environment::Environment;
+use crate::token::Span;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Object>),
+    /// Pairs are keyed by the key's `HashKey` and keep the original key for display.
+    Hash(BTreeMap<HashKey, (Object, Object)>),
+    Null,
+    ReturnValue(Box<Object>),
+    /// A runtime error, with where it happened when that is known.
+    Error(String, Option<Span>),
+    Function {
+        parameters: Vec<Expression>,
+        body: BlockStatement,
+        env: Environment,
+    },
+    Builtin {
+        name: &'static str,
+        function: BuiltinFunction,
+    },
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+}
+
+/// A function lowered to bytecode by the compiler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    /// The names of the local slots, parameters first, for reporting unbound ones.
+    pub local_names: Vec<String>,
+    /// The names of the free variables, in capture order.
+    pub free_names: Vec<String>,
+    pub num_parameters: usize,
+    /// Where the instructions that can fail come from in the source, by offset.
+    pub positions: Vec<(usize, Span)>,
+    /// The function literal, which closures display as, like the evaluator's functions.
+    pub source: String,
+}
+
+/// A compiled function together with the variables of enclosing functions it captured.
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Variable>,
+}
+
+/// A local slot of a call. It is shared with the closures that capture it, so they see
+/// bindings made after they were created, and holds `None` until its `let` runs.
+pub type Variable = Rc<RefCell<Option<Object>>>;
+
+pub type BuiltinFunction = fn(Vec<Object>) -> Object;
+
+/// The values usable as hash keys.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Error(..) => "ERROR",
+            Object::Function { .. } => "FUNCTION",
+            Object::Builtin { .. } => "BUILTIN",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            Object::Closure(_) => "FUNCTION",
+        }
+    }
+
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(..))
+    }
+
+    /// Everything except `false` and `null` is truthy, `0` included.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Boolean(false) | Object::Null)
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => write!(
+                f,
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Object::Hash(pairs) => write!(
+                f,
+                "{{{}}}",
+                pairs
+                    .values()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Object::Null => write!(f, "null"),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Error(message, _) => write!(f, "ERROR: {}", message),
+            Object::Builtin { name, .. } => write!(f, "builtin function {}", name),
+            Object::CompiledFunction(function) => {
+                write!(f, "CompiledFunction[{:p}]", Rc::as_ptr(function))
+            }
+            Object::Closure(closure) => write!(f, "{}", closure.function.source),
+            Object::Function {
+                parameters, body, ..
+            } => write!(
+                f,
+                "{}",
+                Expression::Function {
+                    parameters: parameters.clone(),
+                    body: body.clone(),
+                }
+            ),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::diagnostic;
+use crate::token::{Span, TokenType};
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnexpectedToken {
+        expected: TokenType,
+        found: TokenType,
+        span: Span,
+    },
+    NoPrefixParseFn {
+        token: TokenType,
+        span: Span,
+    },
+    InvalidInteger {
+        literal: String,
+        span: Span,
+    },
+    UnterminatedString {
+        span: Span,
+    },
+    UnterminatedComment {
+        span: Span,
+    },
+    InvalidEscape {
+        sequence: String,
+        span: Span,
+    },
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::NoPrefixParseFn { span, .. }
+            | ParseError::InvalidInteger { span, .. }
+            | ParseError::UnterminatedString { span }
+            | ParseError::UnterminatedComment { span }
+            | ParseError::InvalidEscape { span, .. } => *span,
+        }
+    }
+
+    /// Renders the error followed by the offending source line with the token underlined.
+    ///
+    /// ```text
+    /// error: expected next token to be IDENT, got ASSIGN instead
+    ///  --> 1:5
+    ///   |
+    /// 1 | let = 5;
+    ///   |     ^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        diagnostic::render(&self.to_string(), self.span(), source)
+    }
+
+    /// Like `render`, but points at `path:line:col` so the error can be found in a script file.
+    pub fn render_in_file(&self, path: &str, source: &str) -> String {
+        diagnostic::render_in_file(&self.to_string(), self.span(), path, source)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken {
+                expected, found, ..
+            } => write!(
+                f,
+                "expected next token to be {:?}, got {:?} instead",
+                expected, found
+            ),
+            ParseError::NoPrefixParseFn { token, .. } => {
+                write!(f, "no prefix parse function for {:?} found", token)
+            }
+            ParseError::InvalidInteger { literal, .. } => {
+                write!(f, "could not parse {} as integer", literal)
+            }
+            ParseError::UnterminatedString { .. } => write!(f, "unterminated string literal"),
+            ParseError::UnterminatedComment { .. } => write!(f, "unterminated block comment"),
+            ParseError::InvalidEscape { sequence, .. } => {
+                write!(f, "invalid escape sequence {}", sequence)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::Lexer;
+    use crate::parse_error::ParseError;
+    use crate::parser::Parser;
+    use crate::token::{Span, TokenType};
+
+    fn parse_errors(input: &str) -> Vec<ParseError> {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        parser.parse_program().unwrap_err()
+    }
+
+    #[test]
+    fn test_unexpected_token() {
+        let errors = parse_errors("let = 5;");
+        assert_eq!(
+            errors[0],
+            ParseError::UnexpectedToken {
+                expected: TokenType::IDENT,
+                found: TokenType::ASSIGN,
+                span: Span {
+                    start: 4,
+                    end: 5,
+                    line: 1,
+                    column: 5
+                },
+            }
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "expected next token to be IDENT, got ASSIGN instead"
+        );
+    }
+
+    #[test]
+    fn test_invalid_integer() {
+        let errors = parse_errors("99999999999999999999;");
+        assert_eq!(
+            errors,
+            vec![ParseError::InvalidInteger {
+                literal: "99999999999999999999".to_string(),
+                span: Span {
+                    start: 0,
+                    end: 20,
+                    line: 1,
+                    column: 1
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_string_errors() {
+        let source = "let s = \"a\\qb\";\nlet t = \"open";
+        let errors = parse_errors(source);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "invalid escape sequence \\q");
+        assert_eq!(
+            errors[1].render(source),
+            "error: unterminated string literal\n \
+             --> 2:9\n  \
+             |\n\
+             2 | let t = \"open\n  \
+             |         ^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let source = "let x = 1;\nlet y = x + ;\n";
+        let errors = parse_errors(source);
+        assert_eq!(
+            errors[0].render(source),
+            "error: no prefix parse function for SEMICOLON found\n \
+             --> 2:13\n  \
+             |\n\
+             2 | let y = x + ;\n  \
+             |             ^\n"
+        );
+
+        let source = "let add = fn(x y) { x };";
+        let errors = parse_errors(source);
+        assert_eq!(
+            errors[0].render(source),
+            "error: expected next token to be RPAREN, got IDENT instead\n \
+             --> 1:16\n  \
+             |\n\
+             1 | let add = fn(x y) { x };\n  \
+             |                ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_in_file() {
+        let source = "let = 5;";
+        let errors = parse_errors(source);
+        assert_eq!(
+            errors[0].render_in_file("scripts/main.mk", source),
+            "error: expected next token to be IDENT, got ASSIGN instead\n \
+             --> scripts/main.mk:1:5\n  \
+             |\n\
+             1 | let = 5;\n  \
+             |     ^\n"
+        );
+    }
+}
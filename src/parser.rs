@@ -1,52 +1,142 @@
-use crate::ast::{Expression, Precedence, Program, Statement};
+use crate::ast::{BlockStatement, Expression, Position, Precedence, Program, Statement};
+use crate::cst::{Builder, GreenNode, NodeKind};
 use crate::lexer::Lexer;
+use crate::parse_error::ParseError;
 use crate::token::{Token, TokenType};
+use std::rc::Rc;
 
-struct Parser {
+type PrefixParseFn = fn(&mut Parser) -> Option<Expression>;
+type InfixParseFn = fn(&mut Parser, Expression) -> Option<Expression>;
+
+pub struct Parser {
     lexer: Lexer,
     current_token: Token,
     peek_token: Token,
-    pub errors: Vec<String>,
+    pub errors: Vec<ParseError>,
+    /// Set when the parser also builds a syntax tree; see `with_syntax_tree`.
+    tree: Option<Builder>,
+    // Whether `current_token` has been added to the tree.
+    current_in_tree: bool,
 }
 
 impl Parser {
     pub fn new(mut lexer: Lexer) -> Parser {
-        return Parser {
+        Parser {
             current_token: lexer.next_token(),
             peek_token: lexer.next_token(),
+            errors: lexer.errors.drain(..).collect(),
             lexer, // `lexer` moves here. Therefore we need to complete call `next_token()`
-            errors: vec![],
-        };
+            tree: None,
+            current_in_tree: false,
+        }
+    }
+
+    /// A parser that also records every token, with its trivia, in a lossless syntax tree,
+    /// taken with `into_syntax_tree` once the program is parsed.
+    pub fn with_syntax_tree(lexer: Lexer) -> Parser {
+        let mut parser = Parser::new(lexer);
+        let mut tree = Builder::default();
+        tree.start_node(NodeKind::Program);
+        parser.tree = Some(tree);
+        parser
+    }
+
+    pub fn into_syntax_tree(self) -> Option<Rc<GreenNode>> {
+        self.tree.map(Builder::finish)
     }
 
     fn peek_error(&mut self, token: &TokenType) {
-        self.errors.push(format!(
-            "expected next token to be {:?}, got {:?} instead",
-            token, token,
-        ))
+        self.errors.push(ParseError::UnexpectedToken {
+            expected: token.clone(),
+            found: self.peek_token.token_type.clone(),
+            span: self.peek_token.span,
+        })
     }
 
     fn next_token(&mut self) {
+        self.add_current_to_tree();
+        self.current_in_tree = false;
         self.current_token = self.peek_token.clone();
         self.peek_token = self.lexer.next_token();
+        self.errors.append(&mut self.lexer.errors);
     }
 
-    pub fn parse_program(&mut self) -> Result<Program, String> {
+    /// Parses the whole input, collecting every error instead of stopping at the first one.
+    pub fn parse_program(&mut self) -> Result<Program, Vec<ParseError>> {
         let mut program = Program { statements: vec![] };
 
         while self.current_token.token_type != TokenType::EOF {
             if let Some(statement) = self.parse_statement() {
                 program.statements.push(statement);
+                self.next_token();
             }
-            self.next_token();
         }
-        return Ok(program);
+        // The end of file keeps the trailing trivia.
+        self.add_current_to_tree();
+        self.close_node();
+
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
+        }
+        Ok(program)
     }
+    /// Parses one statement and leaves the parser on its last token. A statement that fails
+    /// to parse is skipped, leaving the parser on the first token after it.
     fn parse_statement(&mut self) -> Option<Statement> {
-        match self.current_token.token_type {
-            TokenType::LET => self.parse_let_statement(),
-            TokenType::RETURN => self.parse_return_statement(),
-            _ => self.parse_expression_statement(),
+        let start = self.current_token.span.start;
+        let depth = self.tree_depth();
+
+        let statement = match self.current_token.token_type {
+            TokenType::LET => {
+                self.start_node(NodeKind::LetStatement);
+                self.parse_let_statement()
+            }
+            TokenType::RETURN => {
+                self.start_node(NodeKind::ReturnStatement);
+                self.parse_return_statement()
+            }
+            _ => {
+                self.start_node(NodeKind::ExpressionStatement);
+                self.parse_expression_statement()
+            }
+        };
+        match statement {
+            Some(_) => self.finish_node(),
+            None => {
+                // The tree keeps what was parsed of the statement and the skipped tokens.
+                while self.tree_depth() > depth + 1 {
+                    self.close_node();
+                }
+                self.start_node(NodeKind::Error);
+                self.synchronize(start);
+                self.close_node();
+                self.close_node();
+            }
+        }
+        statement
+    }
+    /// Skips the rest of a statement that failed to parse, so that one mistake is reported once.
+    /// Stops after a `;`, or before a `}` or statement keyword, ignoring any inside nested braces.
+    /// `start` is where the statement began; the parser always moves past it.
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = 0;
+
+        loop {
+            let moved = self.current_token.span.start != start;
+            match self.current_token.token_type {
+                TokenType::EOF => return,
+                TokenType::SEMICOLON if depth == 0 => {
+                    self.next_token();
+                    return;
+                }
+                TokenType::RBRACE | TokenType::LET | TokenType::RETURN if depth == 0 && moved => {
+                    return;
+                }
+                TokenType::LBRACE => depth += 1,
+                TokenType::RBRACE if depth > 0 => depth -= 1,
+                _ => {}
+            }
+            self.next_token();
         }
     }
     fn parse_expression_statement(&mut self) -> Option<Statement> {
@@ -56,64 +146,374 @@ impl Parser {
         if self.peek_token_is(&TokenType::SEMICOLON) {
             self.next_token();
         }
-        return if let Some(x) = expression {
-            Some(Statement::EXPRESSION(x))
-        } else {
-            None
+        expression.map(Statement::EXPRESSION)
+    }
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        let checkpoint = self.checkpoint();
+        let prefix = match Self::prefix_parse_fn(&self.current_token.token_type) {
+            Some(prefix) => prefix,
+            None => {
+                self.no_prefix_parse_fn_error();
+                return None;
+            }
         };
+        let mut left = prefix(self)?;
+
+        while !self.peek_token_is(&TokenType::SEMICOLON) && precedence < self.peek_precedence() {
+            let infix = match Self::infix_parse_fn(&self.peek_token.token_type) {
+                Some(infix) => infix,
+                None => return Some(left),
+            };
+            self.next_token();
+            let kind = match self.current_token.token_type {
+                TokenType::LPAREN => NodeKind::CallExpression,
+                TokenType::LBRACKET => NodeKind::IndexExpression,
+                _ => NodeKind::InfixExpression,
+            };
+            self.start_node_at(checkpoint, kind);
+            left = infix(self, left)?;
+            self.finish_node();
+        }
+
+        Some(left)
     }
-    fn parse_expression(&mut self, _precedence: Precedence) -> Option<Expression> {
-        let prefix: Option<Expression> = match &self.current_token.token_type {
-            TokenType::IDENT => self.parse_identifier(),
-            TokenType::INT => self.parse_integer(),
+
+    /// The book registers parse functions into maps keyed by token type.
+    /// Matching on the token type does the same job without the bookkeeping.
+    fn prefix_parse_fn(token_type: &TokenType) -> Option<PrefixParseFn> {
+        match token_type {
+            TokenType::IDENT => Some(Self::parse_identifier),
+            TokenType::INT => Some(Self::parse_integer),
+            TokenType::STRING => Some(Self::parse_string_literal),
+            TokenType::TRUE | TokenType::FALSE => Some(Self::parse_boolean),
+            TokenType::LPAREN => Some(Self::parse_grouped_expression),
+            TokenType::IF => Some(Self::parse_if_expression),
+            TokenType::FUNCTION => Some(Self::parse_function_literal),
+            TokenType::LBRACKET => Some(Self::parse_array_literal),
+            // Blocks only follow `if`, `else` and `fn`, which parse them directly,
+            // so a brace in expression position always opens a hash literal.
+            TokenType::LBRACE => Some(Self::parse_hash_literal),
+            TokenType::BANG | TokenType::MINUS => Some(Self::parse_prefix_expression),
             _ => None,
+        }
+    }
+    fn infix_parse_fn(token_type: &TokenType) -> Option<InfixParseFn> {
+        match token_type {
+            TokenType::PLUS
+            | TokenType::MINUS
+            | TokenType::SLASH
+            | TokenType::ASTERISK
+            | TokenType::EQ
+            | TokenType::NotEq
+            | TokenType::LT
+            | TokenType::GT => Some(Self::parse_infix_expression),
+            TokenType::LPAREN => Some(Self::parse_call_expression),
+            TokenType::LBRACKET => Some(Self::parse_index_expression),
+            _ => None,
+        }
+    }
+    fn parse_prefix_expression(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::PrefixExpression);
+        let op = self.current_token.literal.clone();
+        let position = self.current_position();
+        self.next_token();
+        let right = self.parse_expression(Precedence::PREFIX)?;
+        self.finish_node();
+
+        Some(Expression::Prefix {
+            op,
+            right: Box::new(right),
+            position,
+        })
+    }
+    fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+        let op = self.current_token.literal.clone();
+        let position = self.current_position();
+        let precedence = self.current_precedence();
+        self.next_token();
+        let right = self.parse_expression(precedence)?;
+
+        Some(Expression::Infix {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+            position,
+        })
+    }
+    fn peek_precedence(&self) -> Precedence {
+        Precedence::of(&self.peek_token.token_type)
+    }
+    fn current_precedence(&self) -> Precedence {
+        Precedence::of(&self.current_token.token_type)
+    }
+    fn current_position(&self) -> Position {
+        Position(self.current_token.span)
+    }
+    fn no_prefix_parse_fn_error(&mut self) {
+        self.errors.push(ParseError::NoPrefixParseFn {
+            token: self.current_token.token_type.clone(),
+            span: self.current_token.span,
+        })
+    }
+    fn parse_identifier(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::Identifier);
+        self.finish_node();
+        Some(Expression::Identifier(
+            self.current_token.literal.clone(),
+            self.current_position(),
+        ))
+    }
+    fn parse_integer(&mut self) -> Option<Expression> {
+        match self.current_token.literal.parse() {
+            Ok(value) => {
+                self.start_node(NodeKind::Literal);
+                self.finish_node();
+                Some(Expression::Integer(value))
+            }
+            Err(_) => {
+                self.errors.push(ParseError::InvalidInteger {
+                    literal: self.current_token.literal.clone(),
+                    span: self.current_token.span,
+                });
+                None
+            }
+        }
+    }
+    fn parse_string_literal(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::Literal);
+        self.finish_node();
+        Some(Expression::StringLiteral(
+            self.current_token.literal.clone(),
+        ))
+    }
+    fn parse_boolean(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::Literal);
+        self.finish_node();
+        Some(Expression::Boolean(self.current_token_is(TokenType::TRUE)))
+    }
+    fn parse_grouped_expression(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::ParenExpression);
+        self.next_token();
+
+        let expression = self.parse_expression(Precedence::LOWEST)?;
+        if !self.expect_peek(TokenType::RPAREN) {
+            return None;
+        }
+        self.finish_node();
+        Some(expression)
+    }
+    fn parse_if_expression(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::IfExpression);
+        if !self.expect_peek(TokenType::LPAREN) {
+            return None;
+        }
+        self.next_token();
+        let condition = self.parse_expression(Precedence::LOWEST)?;
+
+        if !self.expect_peek(TokenType::RPAREN) {
+            return None;
+        }
+        if !self.expect_peek(TokenType::LBRACE) {
+            return None;
+        }
+        let consequence = self.parse_block_statement();
+
+        let alternative = if self.peek_token_is(&TokenType::ELSE) {
+            self.next_token();
+            self.start_node(NodeKind::ElseBranch);
+            if !self.expect_peek(TokenType::LBRACE) {
+                return None;
+            }
+            let alternative = self.parse_block_statement();
+            self.finish_node();
+            Some(alternative)
+        } else {
+            None
         };
+        self.finish_node();
 
-        return prefix;
+        Some(Expression::If {
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        })
     }
-    fn parse_identifier(&self) -> Option<Expression> {
-        return Some(Expression::Identifier(self.current_token.literal.clone()));
+    fn parse_block_statement(&mut self) -> BlockStatement {
+        self.start_node(NodeKind::Block);
+        let mut block = BlockStatement { statements: vec![] };
+        self.next_token();
+
+        while !self.current_token_is(TokenType::RBRACE) && !self.current_token_is(TokenType::EOF) {
+            if let Some(statement) = self.parse_statement() {
+                block.statements.push(statement);
+                self.next_token();
+            }
+        }
+        self.finish_node();
+        block
     }
-    fn parse_integer(&self) -> Option<Expression> {
-        return Some(Expression::Integer(
-            self.current_token.literal.clone().parse().unwrap(),
-        ));
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::FunctionLiteral);
+        if !self.expect_peek(TokenType::LPAREN) {
+            return None;
+        }
+        self.start_node(NodeKind::ParameterList);
+        let parameters = self.parse_function_parameters()?;
+        self.finish_node();
+
+        if !self.expect_peek(TokenType::LBRACE) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        self.finish_node();
+
+        Some(Expression::Function { parameters, body })
     }
-    fn parse_let_statement(&mut self) -> Option<Statement> {
-        if !self.expect_peek(TokenType::IDENT) {
+    fn parse_function_parameters(&mut self) -> Option<Vec<Expression>> {
+        let mut identifiers = vec![];
+
+        if self.peek_token_is(&TokenType::RPAREN) {
+            self.next_token();
+            return Some(identifiers);
+        }
+
+        identifiers.push(self.parse_name()?);
+        while self.peek_token_is(&TokenType::COMMA) {
+            self.next_token();
+            identifiers.push(self.parse_name()?);
+        }
+
+        if !self.expect_peek(TokenType::RPAREN) {
             return None;
         }
+        Some(identifiers)
+    }
+    fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
+        let position = self.current_position();
+        self.start_node(NodeKind::ArgumentList);
+        let arguments = self.parse_expression_list(TokenType::RPAREN)?;
+        self.finish_node();
+
+        Some(Expression::Call {
+            function: Box::new(function),
+            arguments,
+            position,
+        })
+    }
+    fn parse_array_literal(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::ArrayLiteral);
+        let elements = self.parse_expression_list(TokenType::RBRACKET)?;
+        self.finish_node();
+        Some(Expression::Array(elements))
+    }
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        self.start_node(NodeKind::HashLiteral);
+        let position = self.current_position();
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(&TokenType::RBRACE) {
+            self.next_token();
+            self.start_node(NodeKind::HashPair);
+            let key = self.parse_expression(Precedence::LOWEST)?;
 
-        let name = Expression::Identifier(self.current_token.literal.clone());
+            if !self.expect_peek(TokenType::COLON) {
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::LOWEST)?;
+            self.finish_node();
+            pairs.push((key, value));
+
+            if !self.peek_token_is(&TokenType::RBRACE) && !self.expect_peek(TokenType::COMMA) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenType::RBRACE) {
+            return None;
+        }
+        self.finish_node();
+        Some(Expression::Hash(pairs, position))
+    }
+    fn parse_index_expression(&mut self, left: Expression) -> Option<Expression> {
+        let position = self.current_position();
+        self.next_token();
+        let index = self.parse_expression(Precedence::LOWEST)?;
+
+        if !self.expect_peek(TokenType::RBRACKET) {
+            return None;
+        }
+        Some(Expression::Index {
+            left: Box::new(left),
+            index: Box::new(index),
+            position,
+        })
+    }
+    fn parse_expression_list(&mut self, end: TokenType) -> Option<Vec<Expression>> {
+        let mut list = vec![];
+
+        if self.peek_token_is(&end) {
+            self.next_token();
+            return Some(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(Precedence::LOWEST)?);
+
+        while self.peek_token_is(&TokenType::COMMA) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::LOWEST)?);
+        }
+
+        if !self.expect_peek(end) {
+            return None;
+        }
+        Some(list)
+    }
+    fn parse_let_statement(&mut self) -> Option<Statement> {
+        let name = self.parse_name()?;
         if !self.expect_peek(TokenType::ASSIGN) {
             return None;
         }
-        while !self.current_token_is(TokenType::SEMICOLON) {
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::LOWEST)?;
+
+        if self.peek_token_is(&TokenType::SEMICOLON) {
             self.next_token();
         }
 
-        return Some(Statement::LET {
-            name,
-            value: Expression::Identifier(String::from("")),
-        });
+        Some(Statement::LET { name, value })
     }
     fn parse_return_statement(&mut self) -> Option<Statement> {
-        let statement =
-            Statement::RETURN(Expression::Identifier(self.current_token.literal.clone()));
-
         self.next_token();
 
-        while self.current_token_is(TokenType::SEMICOLON) {
+        let value = self.parse_expression(Precedence::LOWEST)?;
+
+        if self.peek_token_is(&TokenType::SEMICOLON) {
             self.next_token();
         }
 
-        return Some(statement);
+        Some(Statement::RETURN(value))
+    }
+    /// The name bound by `let` or a parameter, which follows the current token.
+    fn parse_name(&mut self) -> Option<Expression> {
+        if !self.expect_peek(TokenType::IDENT) {
+            return None;
+        }
+        self.start_node(NodeKind::Identifier);
+        self.finish_node();
+        Some(Expression::Identifier(
+            self.current_token.literal.clone(),
+            self.current_position(),
+        ))
     }
     fn current_token_is(&self, token: TokenType) -> bool {
-        return self.current_token.token_type == token;
+        self.current_token.token_type == token
     }
     fn peek_token_is(&self, token: &TokenType) -> bool {
-        return self.peek_token.token_type == *token;
+        self.peek_token.token_type == *token
     }
 
     pub fn expect_peek(&mut self, token: TokenType) -> bool {
@@ -122,13 +522,60 @@ impl Parser {
             return true;
         }
         self.peek_error(&token);
-        return false;
+        false
+    }
+
+    // Building the syntax tree. A token goes into the tree once the parser moves past it,
+    // or when the node it ends is finished, so a node opened on the current token
+    // starts with it. Without a tree, these do nothing.
+
+    fn add_current_to_tree(&mut self) {
+        if let Some(tree) = self.tree.as_mut() {
+            if !self.current_in_tree {
+                let span = self.current_token.span;
+                tree.token(
+                    &self.current_token,
+                    &self.lexer.input()[span.start..span.end],
+                );
+                self.current_in_tree = true;
+            }
+        }
+    }
+    fn start_node(&mut self, kind: NodeKind) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.start_node(kind);
+        }
+    }
+    fn checkpoint(&self) -> usize {
+        self.tree.as_ref().map_or(0, Builder::checkpoint)
+    }
+    fn start_node_at(&mut self, checkpoint: usize, kind: NodeKind) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.start_node_at(checkpoint, kind);
+        }
+    }
+    /// Closes the innermost node, which ends with the current token.
+    /// The end of file always belongs to the program, even when a block is left open.
+    fn finish_node(&mut self) {
+        if !self.current_token_is(TokenType::EOF) {
+            self.add_current_to_tree();
+        }
+        self.close_node();
+    }
+    /// Closes the innermost node without adding the current token to it.
+    fn close_node(&mut self) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.finish_node();
+        }
+    }
+    fn tree_depth(&self) -> usize {
+        self.tree.as_ref().map_or(0, Builder::depth)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::ast::{Expression, Statement};
+    use crate::ast::{Expression, Program, Statement};
     use crate::lexer::Lexer;
     use crate::parser::Parser;
 
@@ -161,17 +608,34 @@ mod test {
             3,
             "program.statements does not contain 3 statements"
         );
-        let tests = vec!["x", "y", "foobar"];
+        let tests = ["x", "y", "foobar"];
         for (i, expected_identifier) in tests.iter().enumerate() {
             let statement = &program.statements[i];
-            test_let_statement(statement, &expected_identifier);
+            test_let_statement(statement, expected_identifier);
+        }
+    }
+
+    #[test]
+    fn test_let_and_return_values() {
+        let tests = [
+            ("let x = 5;", "let x = 5;"),
+            ("let y = foobar;", "let y = foobar;"),
+            ("let z = -a * b + c", "let z = (((-a) * b) + c);"),
+            ("return 5;", "return 5"),
+            ("return x + y;", "return (x + y)"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            assert_eq!(program.statements.len(), 1);
+            assert_eq!(program.statements[0].to_string(), expected);
         }
     }
 
     fn test_let_statement(statement: &Statement, expected_identifier: &str) {
         match statement {
             Statement::LET { name, .. } => match name {
-                Expression::Identifier(identifier_name) => {
+                Expression::Identifier(identifier_name, _) => {
                     assert_eq!(
                         identifier_name, expected_identifier,
                         "check let statement name"
@@ -231,7 +695,7 @@ mod test {
         );
         match &program.statements[0] {
             Statement::EXPRESSION(x) => match x {
-                Expression::Identifier(s) => {
+                Expression::Identifier(s, _) => {
                     assert_eq!(s, "foobar");
                 }
                 _ => panic!("expression is not identifier"),
@@ -266,4 +730,403 @@ mod test {
             _ => panic!("statement is not Statement::EXPRESSION"),
         }
     }
+
+    fn parse(input: &str) -> Program {
+        let lexer = Lexer::new(String::from(input));
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        check_parser_errors(&parser);
+        program
+    }
+
+    fn test_literal_expression(expression: &Expression, expected: &str) {
+        match expression {
+            Expression::Identifier(..) | Expression::Integer(_) | Expression::Boolean(_) => {
+                assert_eq!(expression.to_string(), expected);
+            }
+            x => panic!("expression is not a literal. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_parsing_prefix_expressions() {
+        let tests = [
+            ("!5;", "!", "5"),
+            ("-15;", "-", "15"),
+            ("!foobar;", "!", "foobar"),
+        ];
+
+        for (input, expected_op, expected_right) in tests {
+            let program = parse(input);
+            assert_eq!(program.statements.len(), 1);
+            match &program.statements[0] {
+                Statement::EXPRESSION(Expression::Prefix { op, right, .. }) => {
+                    assert_eq!(op, expected_op);
+                    test_literal_expression(right, expected_right);
+                }
+                x => panic!("statement is not a prefix expression. got={:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parsing_infix_expressions() {
+        let tests = [
+            ("5 + 5;", "5", "+", "5"),
+            ("5 - 5;", "5", "-", "5"),
+            ("5 * 5;", "5", "*", "5"),
+            ("5 / 5;", "5", "/", "5"),
+            ("5 > 5;", "5", ">", "5"),
+            ("5 < 5;", "5", "<", "5"),
+            ("5 == 5;", "5", "==", "5"),
+            ("5 != 5;", "5", "!=", "5"),
+            ("a + b;", "a", "+", "b"),
+            ("true == true", "true", "==", "true"),
+            ("true != false", "true", "!=", "false"),
+            ("false == false", "false", "==", "false"),
+        ];
+
+        for (input, expected_left, expected_op, expected_right) in tests {
+            let program = parse(input);
+            assert_eq!(program.statements.len(), 1);
+            match &program.statements[0] {
+                Statement::EXPRESSION(Expression::Infix {
+                    left, op, right, ..
+                }) => {
+                    test_literal_expression(left, expected_left);
+                    assert_eq!(op, expected_op);
+                    test_literal_expression(right, expected_right);
+                }
+                x => panic!("statement is not an infix expression. got={:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence_parsing() {
+        let tests = [
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("a + b + c", "((a + b) + c)"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a * b * c", "((a * b) * c)"),
+            ("a * b / c", "((a * b) / c)"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("3 + 4; -5 * 5", "(3 + 4)\n((-5) * 5)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+            (
+                "3 + 4 * 5 == 3 * 1 + 4 * 5",
+                "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
+            ),
+            ("-a * b + c == d", "((((-a) * b) + c) == d)"),
+            ("true", "true"),
+            ("false", "false"),
+            ("3 > 5 == false", "((3 > 5) == false)"),
+            ("3 < 5 == true", "((3 < 5) == true)"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("2 / (5 + 5)", "(2 / (5 + 5))"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            (
+                "add(a + b + c * d / f + g)",
+                "add((((a + b) + ((c * d) / f)) + g))",
+            ),
+            (
+                "a * [1, 2, 3, 4][b * c] * d",
+                "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+            ),
+            (
+                "add(a * b[2], b[1], 2 * [1, 2][1])",
+                "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            assert_eq!(program.to_string().trim_end(), expected);
+        }
+    }
+
+    #[test]
+    fn test_boolean_expression() {
+        let program = parse("true; false;");
+        assert_eq!(program.statements.len(), 2);
+        for (statement, expected) in program.statements.iter().zip([true, false]) {
+            match statement {
+                Statement::EXPRESSION(Expression::Boolean(value)) => assert_eq!(*value, expected),
+                x => panic!("statement is not a boolean expression. got={:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let program = parse("if (x < y) { x }");
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            Statement::EXPRESSION(Expression::If {
+                condition,
+                consequence,
+                alternative,
+            }) => {
+                assert_eq!(condition.to_string(), "(x < y)");
+                assert_eq!(consequence.statements.len(), 1);
+                assert_eq!(consequence.statements[0].to_string(), "x");
+                assert!(alternative.is_none());
+            }
+            x => panic!("statement is not an if expression. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_if_else_expression() {
+        let program = parse("if (x < y) { x } else { let z = y; z }");
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            Statement::EXPRESSION(Expression::If {
+                condition,
+                consequence,
+                alternative: Some(alternative),
+            }) => {
+                assert_eq!(condition.to_string(), "(x < y)");
+                assert_eq!(consequence.to_string(), "{ x }");
+                assert_eq!(alternative.statements.len(), 2);
+                assert_eq!(alternative.to_string(), "{ let z = y; z }");
+            }
+            x => panic!("statement is not an if/else expression. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_function_literal_parsing() {
+        let program = parse("fn(x, y) { x + y; }");
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            Statement::EXPRESSION(Expression::Function { parameters, body }) => {
+                assert_eq!(parameters.len(), 2);
+                test_literal_expression(&parameters[0], "x");
+                test_literal_expression(&parameters[1], "y");
+                assert_eq!(body.statements.len(), 1);
+                assert_eq!(body.statements[0].to_string(), "(x + y)");
+            }
+            x => panic!("statement is not a function literal. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_function_parameter_parsing() {
+        let tests: [(&str, &[&str]); 3] = [
+            ("fn() {};", &[]),
+            ("fn(x) {};", &["x"]),
+            ("fn(x, y, z) {};", &["x", "y", "z"]),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            match &program.statements[0] {
+                Statement::EXPRESSION(Expression::Function { parameters, .. }) => {
+                    assert_eq!(parameters.len(), expected.len());
+                    for (parameter, expected) in parameters.iter().zip(expected) {
+                        test_literal_expression(parameter, expected);
+                    }
+                }
+                x => panic!("statement is not a function literal. got={:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn test_call_expression_parsing() {
+        let program = parse("add(1, 2 * 3, 4 + 5);");
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            Statement::EXPRESSION(Expression::Call {
+                function,
+                arguments,
+                ..
+            }) => {
+                test_literal_expression(function, "add");
+                assert_eq!(arguments.len(), 3);
+                test_literal_expression(&arguments[0], "1");
+                assert_eq!(arguments[1].to_string(), "(2 * 3)");
+                assert_eq!(arguments[2].to_string(), "(4 + 5)");
+            }
+            x => panic!("statement is not a call expression. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_call_expression_argument_parsing() {
+        let tests = [
+            ("add();", "add()"),
+            ("add(1);", "add(1)"),
+            ("add(f(), g(h(1)));", "add(f(), g(h(1)))"),
+            ("fn(x) { x }(5)", "fn(x) { x }(5)"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            assert_eq!(program.statements.len(), 1);
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_string_literal_expression() {
+        let program = parse(r#""hello\tworld";"#);
+        match &program.statements[0] {
+            Statement::EXPRESSION(Expression::StringLiteral(value)) => {
+                assert_eq!(value, "hello\tworld");
+            }
+            x => panic!("statement is not a string literal. got={:?}", x),
+        }
+        assert_eq!(program.to_string(), "\"hello\\tworld\"\n");
+    }
+
+    #[test]
+    fn test_parsing_array_literals() {
+        let program = parse("[1, 2 * 2, 3 + 3]");
+        match &program.statements[0] {
+            Statement::EXPRESSION(Expression::Array(elements)) => {
+                assert_eq!(elements.len(), 3);
+                test_literal_expression(&elements[0], "1");
+                assert_eq!(elements[1].to_string(), "(2 * 2)");
+                assert_eq!(elements[2].to_string(), "(3 + 3)");
+            }
+            x => panic!("statement is not an array literal. got={:?}", x),
+        }
+
+        let program = parse("[]");
+        match &program.statements[0] {
+            Statement::EXPRESSION(Expression::Array(elements)) => assert!(elements.is_empty()),
+            x => panic!("statement is not an array literal. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_parsing_index_expressions() {
+        let program = parse("myArray[1 + 1]");
+        match &program.statements[0] {
+            Statement::EXPRESSION(Expression::Index { left, index, .. }) => {
+                test_literal_expression(left, "myArray");
+                assert_eq!(index.to_string(), "(1 + 1)");
+            }
+            x => panic!("statement is not an index expression. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_parsing_hash_literals() {
+        let tests = [
+            (
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+                r#"{"one": 1, "two": 2, "three": 3}"#,
+            ),
+            ("{}", "{}"),
+            (
+                r#"{"name": "x", 1: true, false: 0}"#,
+                r#"{"name": "x", 1: true, false: 0}"#,
+            ),
+            (
+                r#"{"one": 0 + 1, "two": 10 - 8, "three": 15 / 5}"#,
+                r#"{"one": (0 + 1), "two": (10 - 8), "three": (15 / 5)}"#,
+            ),
+            ("if (x) { {1: 2} }", "if x { {1: 2} }"),
+            ("fn() { {} }", "fn() { {} }"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            assert_eq!(program.statements.len(), 1);
+            assert_eq!(program.statements[0].to_string(), expected);
+        }
+
+        match &parse(r#"{"a": 1}"#).statements[0] {
+            Statement::EXPRESSION(Expression::Hash(pairs, _)) => {
+                assert_eq!(pairs.len(), 1);
+                assert_eq!(pairs[0].0, Expression::StringLiteral("a".to_string()));
+                test_literal_expression(&pairs[0].1, "1");
+            }
+            x => panic!("statement is not a hash literal. got={:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_no_prefix_parse_function() {
+        let lexer = Lexer::new(String::from("+5;"));
+        let mut parser = Parser::new(lexer);
+        let errors = parser.parse_program().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "no prefix parse function for PLUS found"
+        );
+    }
+    #[test]
+    fn test_error_recovery() {
+        let tests = [
+            // Each broken statement is reported once and parsing resumes after it.
+            (
+                "let x = 5 +;\nlet = 10;\nlet y 3;\nlet z = 1;",
+                vec![
+                    "no prefix parse function for SEMICOLON found",
+                    "expected next token to be IDENT, got ASSIGN instead",
+                    "expected next token to be ASSIGN, got INT instead",
+                ],
+            ),
+            // A missing `;` still resynchronizes at the next statement keyword.
+            (
+                "let x = (1 + 2\nlet y = ;\nreturn y;",
+                vec![
+                    "expected next token to be RPAREN, got LET instead",
+                    "no prefix parse function for SEMICOLON found",
+                ],
+            ),
+            // Errors inside a block do not end the block early.
+            (
+                "let f = fn(x) { let = 1; x * };\nf(1, 2",
+                vec![
+                    "expected next token to be IDENT, got ASSIGN instead",
+                    "no prefix parse function for RBRACE found",
+                    "expected next token to be RPAREN, got EOF instead",
+                ],
+            ),
+            // Braces that belong to the broken statement are skipped as a whole.
+            (
+                "let f = fn(x y) { let a = 1; a };\n}\nlet g = 1;",
+                vec![
+                    "expected next token to be RPAREN, got IDENT instead",
+                    "no prefix parse function for RBRACE found",
+                ],
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(Lexer::new(String::from(input)));
+            let errors = parser.parse_program().unwrap_err();
+            let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+            assert_eq!(messages, expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_error_recovery_keeps_parsing() {
+        let mut parser = Parser::new(Lexer::new(String::from(
+            "let a = 1;\nlet b = * 2;\nlet c = if (a) { a } else { 0 };\nc",
+        )));
+        assert!(parser.parse_program().is_err());
+        assert_eq!(parser.errors.len(), 1);
+
+        let mut parser = Parser::new(Lexer::new(String::from("fn() { 1 + }; 2")));
+        assert!(parser.parse_program().is_err());
+        assert_eq!(parser.errors.len(), 1);
+    }
 }
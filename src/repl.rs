@@ -1,22 +1,185 @@
+use crate::ast::{Program, Statement};
+use crate::compiler::Compiler;
+use crate::disassembler::disassemble;
+use crate::environment::Environment;
+use crate::evaluator::eval;
 use crate::lexer::Lexer;
-use crate::token::TokenType;
-use std::io::Stdin;
+use crate::object::Object;
+use crate::parse_error::ParseError;
+use crate::parser::Parser;
+use crate::symbol_table::SymbolTable;
+use crate::vm::{GLOBALS_SIZE, VM};
+use crate::Engine;
+use std::io::{stdout, Stdin, Write};
+use std::mem;
+
+const PROMPT: &str = ">> ";
+/// `:disasm <code>` prints the bytecode of the code instead of evaluating it.
+const DISASM_COMMAND: &str = ":disasm";
+
+/// A line of input: code to run, or a `:` command. Monkey code never starts with `:`.
+#[derive(Debug, PartialEq)]
+enum Input<'a> {
+    Code(&'a str),
+    Disasm(&'a str),
+}
+
+fn read_input(line: &str) -> Result<Input<'_>, String> {
+    let trimmed = line.trim_start();
+    if !trimmed.starts_with(':') {
+        return Ok(Input::Code(line));
+    }
+    let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let (command, rest) = trimmed.split_at(end);
+    if command == DISASM_COMMAND {
+        return Ok(Input::Disasm(rest));
+    }
+    Err(format!(
+        "unknown command {}, expected {} <code>",
+        command, DISASM_COMMAND
+    ))
+}
+
+pub fn start(stdin: Stdin, engine: Engine) {
+    let mut env = Environment::new();
+    // The names and constants compiled so far, and the globals, when running on the VM.
+    let mut compiler_state = Compiler::new().into_state();
+    let mut globals = vec![None; GLOBALS_SIZE];
 
-pub fn start(stdin: Stdin) {
     loop {
+        print!("{}", PROMPT);
+        stdout().flush().expect("failed to flush stdout");
+
         let mut s = String::new();
         stdin.read_line(&mut s).expect("failed to read stdin");
 
         if s.is_empty() {
             return;
         }
-        let mut lexer = Lexer::new(s);
-        loop {
-            let token = lexer.next_token();
-            match token.token_type {
-                TokenType::EOF => break,
-                _ => println!("{:?}", token),
+        let (code, disasm) = match read_input(&s) {
+            Ok(Input::Code(code)) => (code.to_string(), false),
+            Ok(Input::Disasm(code)) => (code.to_string(), true),
+            Err(message) => {
+                println!("{}", message);
+                continue;
+            }
+        };
+
+        let lexer = Lexer::new(code.clone());
+        let mut parser = Parser::new(lexer);
+        let program = match parser.parse_program() {
+            Ok(program) => program,
+            Err(errors) => {
+                print_parser_errors(&errors, &code);
+                continue;
             }
+        };
+
+        if disasm {
+            print_disassembly(&program, &compiler_state);
+            continue;
         }
+        let evaluated = match engine {
+            Engine::Evaluator => eval(&program, &mut env),
+            Engine::VM => execute(&program, &mut compiler_state, &mut globals),
+        };
+        // A `let` binds a name and has nothing worth printing, unless it failed.
+        let is_let = matches!(
+            program.statements.last(),
+            None | Some(Statement::LET { .. })
+        );
+        if !is_let || evaluated.is_error() {
+            println!("{}", evaluated);
+        }
+    }
+}
+
+type CompilerState = (SymbolTable, Vec<Object>);
+
+/// Compiles the program on top of the session's state and runs it on the session's
+/// globals. The state is kept only if the program compiled; globals bound before
+/// a runtime error stay bound, as they do in the evaluator.
+fn execute(
+    program: &Program,
+    state: &mut CompilerState,
+    globals: &mut Vec<Option<Object>>,
+) -> Object {
+    let mut compiler = Compiler::new_with_state(state.0.clone(), state.1.clone());
+    if let Err(message) = compiler.compile(program) {
+        return Object::Error(message, None);
+    }
+    let bytecode = compiler.bytecode();
+    *state = compiler.into_state();
+
+    let mut vm = VM::new_with_globals_store(bytecode, mem::take(globals));
+    let result = vm
+        .run()
+        .map_err(|message| Object::Error(message, vm.position()));
+    let value = vm.last_popped_stack_elem();
+    *globals = vm.into_globals_store();
+    result.map_or_else(|error| error, |()| value)
+}
+
+/// Compiles on top of the session's names without keeping what the program adds.
+/// Names the evaluator bound compile to global slots like any other global.
+fn print_disassembly(program: &Program, state: &CompilerState) {
+    let mut compiler = Compiler::new_with_state(state.0.clone(), state.1.clone());
+    match compiler.compile(program) {
+        Ok(()) => print!("{}", disassemble(&compiler.bytecode())),
+        Err(message) => println!("compile error: {}", message),
+    }
+}
+
+fn print_parser_errors(errors: &[ParseError], source: &str) {
+    for error in errors {
+        println!("{}", error.render(source));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::repl::{execute, read_input, Input};
+    use crate::vm::GLOBALS_SIZE;
+
+    #[test]
+    fn test_failed_compilation_keeps_state() {
+        let mut state = Compiler::new().into_state();
+        let mut globals = vec![None; GLOBALS_SIZE];
+        let mut run = |input: &str| {
+            let program = Parser::new(Lexer::new(input.to_string()))
+                .parse_program()
+                .unwrap();
+            execute(&program, &mut state, &mut globals).to_string()
+        };
+
+        assert_eq!(run("let a = 1; a"), "1");
+        let arguments = vec!["1"; 300].join(", ");
+        assert_eq!(
+            run(&format!("let f = fn() {{ len({}) }};", arguments)),
+            "ERROR: operand too large: OpCall takes at most 255, got 300"
+        );
+        assert_eq!(run("let b = a + 1; b"), "2");
+        assert_eq!(state.0.definition_names(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_read_input() {
+        assert_eq!(read_input("let a = 1;\n"), Ok(Input::Code("let a = 1;\n")));
+        assert_eq!(
+            read_input(" :disasm 1 + 2\n"),
+            Ok(Input::Disasm(" 1 + 2\n"))
+        );
+        assert_eq!(read_input(":disasm\n"), Ok(Input::Disasm("\n")));
+        assert_eq!(
+            read_input(":disasmfoo\n"),
+            Err("unknown command :disasmfoo, expected :disasm <code>".to_string())
+        );
+        assert_eq!(
+            read_input(":quit\n"),
+            Err("unknown command :quit, expected :disasm <code>".to_string())
+        );
     }
 }
@@ -0,0 +1,490 @@
+use crate::ast::{BlockStatement, Expression, Position, Program, Statement};
+use crate::cst::{LineIndex, NodeKind, Parse, SyntaxNode};
+use crate::parse_error::ParseError;
+use crate::token::{Span, Token};
+use serde_json::{json, Map, Value};
+
+// A stable JSON encoding of tokens and of the AST, for inspecting parser output with other tools.
+//
+// Every AST node is an object with a "type", the name of its variant, and a "span".
+// The AST only records the token each node is reported at, so the spans come from the
+// syntax tree the program was lowered from, which has a node for each AST node (plus parentheses, which only group).
+
+fn span_to_json(span: Span) -> Value {
+    json!({
+        "start": span.start,
+        "end": span.end,
+        "line": span.line,
+        "column": span.column,
+    })
+}
+
+/// `[{"type": "LET", "literal": "let", "span": {...}}, ...]`
+pub fn tokens_to_json(tokens: &[Token]) -> Value {
+    tokens
+        .iter()
+        .map(|token| {
+            json!({
+                "type": format!("{:?}", token.token_type),
+                "literal": token.literal,
+                "span": span_to_json(token.span),
+            })
+        })
+        .collect()
+}
+
+/// Lowers the parsed program and encodes it, with the spans of its syntax tree.
+pub fn program_to_json(parse: &Parse) -> Result<Value, Vec<ParseError>> {
+    let program = parse.lower()?;
+    let syntax = parse.syntax();
+    let encoder = Encoder {
+        lines: parse.line_index(),
+    };
+    Ok(json!({
+        "type": "Program",
+        "span": span_to_json(syntax.span(parse.line_index())),
+        "statements": encoder.statements(&program.statements, &syntax),
+    }))
+}
+
+struct Encoder<'a> {
+    lines: &'a LineIndex,
+}
+
+impl Encoder<'_> {
+    fn node(&self, node_type: &str, node: &SyntaxNode, fields: Vec<(&str, Value)>) -> Value {
+        let mut object = Map::new();
+        object.insert("type".to_string(), json!(node_type));
+        object.insert("span".to_string(), span_to_json(node.span(self.lines)));
+        for (key, value) in fields {
+            object.insert(key.to_string(), value);
+        }
+        Value::Object(object)
+    }
+
+    fn statements(&self, statements: &[Statement], node: &SyntaxNode) -> Value {
+        statements
+            .iter()
+            .zip(node.child_nodes())
+            .map(|(statement, node)| self.statement(statement, &node))
+            .collect()
+    }
+    fn statement(&self, statement: &Statement, node: &SyntaxNode) -> Value {
+        let children = node.child_nodes();
+        match statement {
+            Statement::LET { name, value } => self.node(
+                "LetStatement",
+                node,
+                vec![
+                    ("name", self.expression(name, &children[0])),
+                    ("value", self.expression(value, &children[1])),
+                ],
+            ),
+            Statement::RETURN(value) => self.node(
+                "ReturnStatement",
+                node,
+                vec![("value", self.expression(value, &children[0]))],
+            ),
+            Statement::EXPRESSION(expression) => self.node(
+                "ExpressionStatement",
+                node,
+                vec![("expression", self.expression(expression, &children[0]))],
+            ),
+        }
+    }
+    fn block(&self, block: &BlockStatement, node: &SyntaxNode) -> Value {
+        self.node(
+            "Block",
+            node,
+            vec![("statements", self.statements(&block.statements, node))],
+        )
+    }
+    fn expressions(&self, expressions: &[Expression], nodes: Vec<SyntaxNode>) -> Value {
+        expressions
+            .iter()
+            .zip(nodes)
+            .map(|(expression, node)| self.expression(expression, &node))
+            .collect()
+    }
+    fn expression(&self, expression: &Expression, node: &SyntaxNode) -> Value {
+        let children = node.child_nodes();
+        if node.kind() == NodeKind::ParenExpression {
+            return self.expression(expression, &children[0]);
+        }
+
+        match expression {
+            Expression::Identifier(name, _) => {
+                self.node("Identifier", node, vec![("name", json!(name))])
+            }
+            Expression::Integer(value) => self.node("Integer", node, vec![("value", json!(value))]),
+            Expression::Boolean(value) => self.node("Boolean", node, vec![("value", json!(value))]),
+            Expression::StringLiteral(value) => {
+                self.node("StringLiteral", node, vec![("value", json!(value))])
+            }
+            Expression::Prefix { op, right, .. } => self.node(
+                "Prefix",
+                node,
+                vec![
+                    ("operator", json!(op)),
+                    ("right", self.expression(right, &children[0])),
+                ],
+            ),
+            Expression::Infix {
+                left, op, right, ..
+            } => self.node(
+                "Infix",
+                node,
+                vec![
+                    ("left", self.expression(left, &children[0])),
+                    ("operator", json!(op)),
+                    ("right", self.expression(right, &children[1])),
+                ],
+            ),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+            } => self.node(
+                "If",
+                node,
+                vec![
+                    ("condition", self.expression(condition, &children[0])),
+                    ("consequence", self.block(consequence, &children[1])),
+                    (
+                        "alternative",
+                        match alternative {
+                            Some(block) => self.block(block, &children[2].child_nodes()[0]),
+                            None => Value::Null,
+                        },
+                    ),
+                ],
+            ),
+            Expression::Function { parameters, body } => self.node(
+                "Function",
+                node,
+                vec![
+                    (
+                        "parameters",
+                        self.expressions(parameters, children[0].child_nodes()),
+                    ),
+                    ("body", self.block(body, &children[1])),
+                ],
+            ),
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => self.node(
+                "Call",
+                node,
+                vec![
+                    ("function", self.expression(function, &children[0])),
+                    (
+                        "arguments",
+                        self.expressions(arguments, children[1].child_nodes()),
+                    ),
+                ],
+            ),
+            Expression::Array(elements) => self.node(
+                "Array",
+                node,
+                vec![("elements", self.expressions(elements, children))],
+            ),
+            Expression::Hash(pairs, _) => {
+                let pairs = pairs
+                    .iter()
+                    .zip(children)
+                    .map(|((key, value), pair)| {
+                        let pair = pair.child_nodes();
+                        json!({
+                            "key": self.expression(key, &pair[0]),
+                            "value": self.expression(value, &pair[1]),
+                        })
+                    })
+                    .collect();
+                self.node("Hash", node, vec![("pairs", pairs)])
+            }
+            Expression::Index { left, index, .. } => self.node(
+                "Index",
+                node,
+                vec![
+                    ("left", self.expression(left, &children[0])),
+                    ("index", self.expression(index, &children[1])),
+                ],
+            ),
+        }
+    }
+}
+
+/// Decodes what `program_to_json` produced, for tools that build or rewrite programs as JSON.
+/// Spans are optional and ignored, so the decoded positions are all the default.
+// The command line only encodes, so within this crate only the tests decode.
+#[allow(dead_code)]
+pub fn program_from_json(json: &Value) -> Result<Program, String> {
+    expect_type(json, "Program")?;
+    Ok(Program {
+        statements: statements_from_json(field(json, "statements")?)?,
+    })
+}
+
+fn field<'a>(json: &'a Value, key: &str) -> Result<&'a Value, String> {
+    json.get(key)
+        .ok_or_else(|| format!("missing field \"{}\" in {}", key, json))
+}
+fn node_type(json: &Value) -> Result<&str, String> {
+    match field(json, "type")? {
+        Value::String(node_type) => Ok(node_type),
+        other => Err(format!("expected a string type, got {}", other)),
+    }
+}
+fn expect_type(json: &Value, expected: &str) -> Result<(), String> {
+    let node_type = node_type(json)?;
+    if node_type != expected {
+        return Err(format!("expected a {}, got {}", expected, node_type));
+    }
+    Ok(())
+}
+fn array(json: &Value) -> Result<&[Value], String> {
+    match json {
+        Value::Array(values) => Ok(values),
+        other => Err(format!("expected an array, got {}", other)),
+    }
+}
+fn string(json: &Value) -> Result<String, String> {
+    match json {
+        Value::String(value) => Ok(value.clone()),
+        other => Err(format!("expected a string, got {}", other)),
+    }
+}
+
+fn statements_from_json(json: &Value) -> Result<Vec<Statement>, String> {
+    array(json)?.iter().map(statement_from_json).collect()
+}
+fn statement_from_json(json: &Value) -> Result<Statement, String> {
+    match node_type(json)? {
+        "LetStatement" => Ok(Statement::LET {
+            name: expression_from_json(field(json, "name")?)?,
+            value: expression_from_json(field(json, "value")?)?,
+        }),
+        "ReturnStatement" => Ok(Statement::RETURN(expression_from_json(field(
+            json, "value",
+        )?)?)),
+        "ExpressionStatement" => Ok(Statement::EXPRESSION(expression_from_json(field(
+            json,
+            "expression",
+        )?)?)),
+        other => Err(format!("unknown statement type {}", other)),
+    }
+}
+fn block_from_json(json: &Value) -> Result<BlockStatement, String> {
+    expect_type(json, "Block")?;
+    Ok(BlockStatement {
+        statements: statements_from_json(field(json, "statements")?)?,
+    })
+}
+fn expressions_from_json(json: &Value) -> Result<Vec<Expression>, String> {
+    array(json)?.iter().map(expression_from_json).collect()
+}
+fn expression_from_json(json: &Value) -> Result<Expression, String> {
+    let boxed = |key: &str| -> Result<Box<Expression>, String> {
+        Ok(Box::new(expression_from_json(field(json, key)?)?))
+    };
+
+    match node_type(json)? {
+        "Identifier" => Ok(Expression::Identifier(
+            string(field(json, "name")?)?,
+            Position::default(),
+        )),
+        "Integer" => {
+            let value = field(json, "value")?;
+            match value.as_i64() {
+                Some(value) => Ok(Expression::Integer(value)),
+                None => Err(format!("expected an integer, got {}", value)),
+            }
+        }
+        "Boolean" => match field(json, "value")? {
+            Value::Bool(value) => Ok(Expression::Boolean(*value)),
+            other => Err(format!("expected a boolean, got {}", other)),
+        },
+        "StringLiteral" => Ok(Expression::StringLiteral(string(field(json, "value")?)?)),
+        "Prefix" => Ok(Expression::Prefix {
+            op: string(field(json, "operator")?)?,
+            right: boxed("right")?,
+            position: Position::default(),
+        }),
+        "Infix" => Ok(Expression::Infix {
+            left: boxed("left")?,
+            op: string(field(json, "operator")?)?,
+            right: boxed("right")?,
+            position: Position::default(),
+        }),
+        "If" => Ok(Expression::If {
+            condition: boxed("condition")?,
+            consequence: block_from_json(field(json, "consequence")?)?,
+            alternative: match json.get("alternative") {
+                None | Some(Value::Null) => None,
+                Some(block) => Some(block_from_json(block)?),
+            },
+        }),
+        "Function" => Ok(Expression::Function {
+            parameters: expressions_from_json(field(json, "parameters")?)?,
+            body: block_from_json(field(json, "body")?)?,
+        }),
+        "Call" => Ok(Expression::Call {
+            function: boxed("function")?,
+            arguments: expressions_from_json(field(json, "arguments")?)?,
+            position: Position::default(),
+        }),
+        "Array" => Ok(Expression::Array(expressions_from_json(field(
+            json, "elements",
+        )?)?)),
+        "Hash" => Ok(Expression::Hash(
+            array(field(json, "pairs")?)?
+                .iter()
+                .map(|pair| {
+                    Ok((
+                        expression_from_json(field(pair, "key")?)?,
+                        expression_from_json(field(pair, "value")?)?,
+                    ))
+                })
+                .collect::<Result<_, String>>()?,
+            Position::default(),
+        )),
+        "Index" => Ok(Expression::Index {
+            left: boxed("left")?,
+            index: boxed("index")?,
+            position: Position::default(),
+        }),
+        other => Err(format!("unknown expression type {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ast::strip_positions;
+    use crate::cst;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::serialize::{program_from_json, program_to_json, tokens_to_json};
+    use crate::token::TokenType;
+    use serde_json::{json, Value};
+
+    const GOLDEN_SOURCE: &str = include_str!("../testdata/program.mk");
+    const GOLDEN_JSON: &str = include_str!("../testdata/program.json");
+
+    fn program_json(source: &str) -> Value {
+        program_to_json(&cst::parse(source)).unwrap()
+    }
+
+    #[test]
+    fn test_tokens_to_json() {
+        let mut lexer = Lexer::new(String::from("let x =\n \"é\";"));
+        let mut tokens = vec![];
+        loop {
+            let token = lexer.next_token();
+            let is_eof = token.token_type == TokenType::EOF;
+            tokens.push(token);
+            if is_eof {
+                break;
+            }
+        }
+
+        let span = |start, end, line, column| json!({"start": start, "end": end, "line": line, "column": column});
+        assert_eq!(
+            tokens_to_json(&tokens),
+            json!([
+                {"type": "LET", "literal": "let", "span": span(0, 3, 1, 1)},
+                {"type": "IDENT", "literal": "x", "span": span(4, 5, 1, 5)},
+                {"type": "ASSIGN", "literal": "=", "span": span(6, 7, 1, 7)},
+                {"type": "STRING", "literal": "é", "span": span(9, 13, 2, 2)},
+                {"type": "SEMICOLON", "literal": ";", "span": span(13, 14, 2, 5)},
+                {"type": "EOF", "literal": "", "span": span(14, 14, 2, 6)},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_program_to_json() {
+        assert_eq!(
+            program_json("let x = (1 + y);").to_string(),
+            concat!(
+                r#"{"type":"Program","span":{"start":0,"end":16,"line":1,"column":1},"statements":["#,
+                r#"{"type":"LetStatement","span":{"start":0,"end":16,"line":1,"column":1},"#,
+                r#""name":{"type":"Identifier","span":{"start":4,"end":5,"line":1,"column":5},"name":"x"},"#,
+                r#""value":{"type":"Infix","span":{"start":9,"end":14,"line":1,"column":10},"#,
+                r#""left":{"type":"Integer","span":{"start":9,"end":10,"line":1,"column":10},"value":1},"#,
+                r#""operator":"+","#,
+                r#""right":{"type":"Identifier","span":{"start":13,"end":14,"line":1,"column":14},"name":"y"}}}]}"#,
+            )
+        );
+        assert_eq!(
+            program_to_json(&cst::parse("let = 1;")).unwrap_err()[0].to_string(),
+            "expected next token to be IDENT, got ASSIGN instead"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let sources = [
+            "let add = fn(a, b) { return a + b; }; add(1, -2)",
+            "if (x < 10) { \"small\\n\" } else { [1, 2][0] }",
+            "let h = {\"a\": true, 1: fn() {}}; h[\"a\"] != !false",
+            "if (a) { b }",
+        ];
+        for source in sources {
+            let expected = Parser::new(Lexer::new(source.to_string()))
+                .parse_program()
+                .unwrap();
+            let pretty = serde_json::to_string_pretty(&program_json(source)).unwrap();
+            let json = serde_json::from_str(&pretty).unwrap();
+            assert_eq!(
+                program_from_json(&json).unwrap(),
+                strip_positions(&expected),
+                "source: {}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_golden() {
+        let json = serde_json::from_str(GOLDEN_JSON).unwrap();
+        let expected = Parser::new(Lexer::new(GOLDEN_SOURCE.to_string()))
+            .parse_program()
+            .unwrap();
+        assert_eq!(
+            program_from_json(&json).unwrap(),
+            strip_positions(&expected)
+        );
+        assert_eq!(
+            serde_json::to_string_pretty(&program_json(GOLDEN_SOURCE)).unwrap() + "\n",
+            GOLDEN_JSON
+        );
+    }
+
+    #[test]
+    fn test_program_from_json_errors() {
+        let tests = [
+            (
+                r#"{"type": "Block", "statements": []}"#,
+                "expected a Program, got Block",
+            ),
+            (
+                r#"{"type": "Program"}"#,
+                r#"missing field "statements" in {"type":"Program"}"#,
+            ),
+            (
+                r#"{"type": "Program", "statements": [{"type": "ExpressionStatement", "expression": {"type": "Float"}}]}"#,
+                "unknown expression type Float",
+            ),
+            (
+                r#"{"type": "Program", "statements": [{"type": "ReturnStatement", "value": {"type": "Integer", "value": "1"}}]}"#,
+                r#"expected an integer, got "1""#,
+            ),
+        ];
+        for (input, expected) in tests {
+            let json = serde_json::from_str(input).unwrap();
+            assert_eq!(program_from_json(&json).unwrap_err(), expected);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    Builtin,
+    Free,
+    /// The name a function is bound to, resolved inside its own body for recursion.
+    Function,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    pub outer: Option<Box<SymbolTable>>,
+    store: HashMap<String, Symbol>,
+    pub num_definitions: usize,
+    /// Symbols of enclosing functions this function captures, in capture order.
+    pub free_symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> SymbolTable {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        }
+    }
+
+    /// Binds a name in this scope. Rebinding a name reuses its slot,
+    /// which is how `let x = x + 1;` keeps referring to a single variable.
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = if self.outer.is_none() {
+            SymbolScope::Global
+        } else {
+            SymbolScope::Local
+        };
+
+        if let Some(symbol) = self.store.get(name) {
+            if symbol.scope == scope {
+                return symbol.clone();
+            }
+        }
+
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        self.num_definitions += 1;
+        symbol
+    }
+
+    /// Reserves a global slot for a name the program binds later. Reading it before
+    /// that `let` runs is a runtime error, so functions can refer to globals defined after them.
+    pub fn define_global(&mut self, name: &str) -> Symbol {
+        match self.outer.as_mut() {
+            Some(outer) => outer.define_global(name),
+            None => self.define(name),
+        }
+    }
+
+    /// The names of the slots defined in this scope, by index.
+    pub fn definition_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.num_definitions];
+        for symbol in self.store.values() {
+            if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local) {
+                names[symbol.index] = symbol.name.clone();
+            }
+        }
+        names
+    }
+
+    pub fn define_builtin(&mut self, index: usize, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Builtin,
+            index,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+
+    /// Locals of enclosing functions become free variables of this one.
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+
+        let symbol = self.outer.as_mut()?.resolve(name)?;
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::Builtin => Some(symbol),
+            _ => Some(self.define_free(symbol)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            scope,
+            index,
+        }
+    }
+
+    #[test]
+    fn test_define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        global.define_builtin(0, "len");
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+        assert_eq!(local.define("a"), symbol("a", SymbolScope::Local, 1));
+
+        let mut nested = SymbolTable::new_enclosed(local);
+        assert_eq!(nested.define("d"), symbol("d", SymbolScope::Local, 0));
+
+        assert_eq!(
+            nested.resolve("b"),
+            Some(symbol("b", SymbolScope::Global, 1))
+        );
+        assert_eq!(
+            nested.resolve("len"),
+            Some(symbol("len", SymbolScope::Builtin, 0))
+        );
+        assert_eq!(
+            nested.resolve("d"),
+            Some(symbol("d", SymbolScope::Local, 0))
+        );
+        assert_eq!(nested.resolve("c"), Some(symbol("c", SymbolScope::Free, 0)));
+        assert_eq!(nested.resolve("a"), Some(symbol("a", SymbolScope::Free, 1)));
+        assert_eq!(nested.resolve("e"), None);
+        assert_eq!(
+            nested.free_symbols,
+            vec![
+                symbol("c", SymbolScope::Local, 0),
+                symbol("a", SymbolScope::Local, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_define_global() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+        let mut local = SymbolTable::new_enclosed(global);
+        local.define("b");
+
+        assert_eq!(
+            local.define_global("later"),
+            symbol("later", SymbolScope::Global, 1)
+        );
+        assert_eq!(local.definition_names(), vec!["b"]);
+
+        let mut global = *local.outer.unwrap();
+        assert_eq!(
+            global.define("later"),
+            symbol("later", SymbolScope::Global, 1)
+        );
+        assert_eq!(global.definition_names(), vec!["a", "later"]);
+    }
+
+    #[test]
+    fn test_function_name() {
+        let mut local = SymbolTable::new_enclosed(SymbolTable::new());
+        local.define_function_name("f");
+        assert_eq!(
+            local.resolve("f"),
+            Some(symbol("f", SymbolScope::Function, 0))
+        );
+
+        // A parameter with the same name shadows the function.
+        local.define("f");
+        assert_eq!(local.resolve("f"), Some(symbol("f", SymbolScope::Local, 0)));
+    }
+}
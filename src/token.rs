@@ -1,15 +1,55 @@
+use std::fmt;
+use std::fmt::Formatter;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Token {
     pub(crate) token_type: TokenType,
     pub(crate) literal: String,
+    pub(crate) span: Span,
+    /// Whitespace and comments before the token, kept only when the lexer retains trivia.
+    pub(crate) leading_trivia: Vec<Trivia>,
+}
+
+/// Where a token sits in the source.
+/// `start` and `end` are byte offsets, `line` and `column` are 1-based and point at `start`.
+/// Columns count characters, not bytes.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
+// The token names are the book's.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone, Ord, PartialOrd, Eq)]
 pub enum TokenType {
     ILLEGAL,
     EOF,
     IDENT,
     INT,
+    STRING,
     // operator
     ASSIGN,
     PLUS,
@@ -23,10 +63,13 @@ pub enum TokenType {
     //
     COMMA,
     SEMICOLON,
+    COLON,
     LPAREN,
     RPAREN,
     LBRACE,
     RBRACE,
+    LBRACKET,
+    RBRACKET,
 
     // keyword
     FUNCTION,
@@ -40,8 +83,8 @@ pub enum TokenType {
     NotEq,
 }
 
-pub fn lookup_ident(ident: &String) -> TokenType {
-    match ident.as_str() {
+pub fn lookup_ident(ident: &str) -> TokenType {
+    match ident {
         "fn" => TokenType::FUNCTION,
         "let" => TokenType::LET,
         "true" => TokenType::TRUE,
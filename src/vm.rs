@@ -0,0 +1,543 @@
+use crate::builtins::BUILTINS;
+use crate::code::{read_u16, Opcode};
+use crate::compiler::Bytecode;
+use crate::evaluator::{eval_index_expression, eval_infix_expression, eval_prefix_expression};
+use crate::object::{Closure, CompiledFunction, Object, Variable};
+use crate::token::Span;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+const STACK_SIZE: usize = 2048;
+pub const GLOBALS_SIZE: usize = 65536;
+pub const MAX_FRAMES: usize = 1024;
+
+/// The call of a closure: where its instructions are read, where its arguments
+/// were on the stack, and its locals.
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base_pointer: usize,
+    locals: Vec<Variable>,
+}
+
+pub struct VM {
+    constants: Vec<Object>,
+    stack: Vec<Object>,
+    /// Always points to the next free slot. The top of the stack is `stack[sp - 1]`.
+    sp: usize,
+    /// Slots no `let` has bound yet are `None`.
+    globals: Vec<Option<Object>>,
+    global_names: Vec<String>,
+    frames: Vec<Frame>,
+    /// Where the instruction being run starts in the current frame, to locate errors.
+    instruction: usize,
+}
+
+impl VM {
+    pub fn new(bytecode: Bytecode) -> VM {
+        VM::new_with_globals_store(bytecode, vec![None; GLOBALS_SIZE])
+    }
+
+    /// Runs with the globals of an earlier run, as the REPL does.
+    pub fn new_with_globals_store(bytecode: Bytecode, globals: Vec<Option<Object>>) -> VM {
+        let main_function = CompiledFunction {
+            instructions: bytecode.instructions,
+            local_names: vec![],
+            free_names: vec![],
+            num_parameters: 0,
+            positions: bytecode.positions,
+            source: String::new(),
+        };
+        let main_closure = Closure {
+            function: Rc::new(main_function),
+            free: vec![],
+        };
+
+        VM {
+            constants: bytecode.constants,
+            stack: vec![Object::Null; STACK_SIZE],
+            sp: 0,
+            globals,
+            global_names: bytecode.global_names,
+            frames: vec![Frame {
+                closure: Rc::new(main_closure),
+                ip: 0,
+                base_pointer: 0,
+                locals: vec![],
+            }],
+            instruction: 0,
+        }
+    }
+
+    pub fn into_globals_store(self) -> Vec<Option<Object>> {
+        self.globals
+    }
+
+    /// The value of the last expression statement, which has just been popped.
+    pub fn last_popped_stack_elem(&self) -> Object {
+        self.stack[self.sp].clone()
+    }
+
+    pub fn run(&mut self) -> Result<(), String> {
+        while self.current_frame().ip < self.current_frame().closure.function.instructions.len() {
+            let ip = self.current_frame().ip;
+            let op = Opcode::from_byte(self.current_instructions()[ip])
+                .ok_or_else(|| format!("unknown opcode {}", self.current_instructions()[ip]))?;
+            self.instruction = ip;
+            self.current_frame_mut().ip += 1;
+
+            match op {
+                Opcode::OpConstant => {
+                    let index = self.read_u16_operand();
+                    self.push(self.constants[index].clone())?;
+                }
+                Opcode::OpPop => {
+                    self.pop();
+                }
+                Opcode::OpAdd
+                | Opcode::OpSub
+                | Opcode::OpMul
+                | Opcode::OpDiv
+                | Opcode::OpEqual
+                | Opcode::OpNotEqual
+                | Opcode::OpGreaterThan
+                | Opcode::OpLessThan => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = eval_infix_expression(Self::infix_operator(op), left, right);
+                    self.push_result(result)?;
+                }
+                Opcode::OpMinus => {
+                    let right = self.pop();
+                    self.push_result(eval_prefix_expression("-", right))?;
+                }
+                Opcode::OpBang => {
+                    let right = self.pop();
+                    self.push_result(eval_prefix_expression("!", right))?;
+                }
+                Opcode::OpTrue => self.push(Object::Boolean(true))?,
+                Opcode::OpFalse => self.push(Object::Boolean(false))?,
+                Opcode::OpNull => self.push(Object::Null)?,
+                Opcode::OpJump => {
+                    let position = self.read_u16_operand();
+                    self.current_frame_mut().ip = position;
+                }
+                Opcode::OpJumpNotTruthy => {
+                    let position = self.read_u16_operand();
+                    if !self.pop().is_truthy() {
+                        self.current_frame_mut().ip = position;
+                    }
+                }
+                Opcode::OpSetGlobal => {
+                    let index = self.read_u16_operand();
+                    self.globals[index] = Some(self.pop());
+                }
+                Opcode::OpGetGlobal => {
+                    let index = self.read_u16_operand();
+                    let value = self.globals[index].clone().ok_or_else(|| {
+                        format!("identifier not found: {}", self.global_names[index])
+                    })?;
+                    self.push(value)?;
+                }
+                Opcode::OpSetLocal => {
+                    let index = self.read_u8_operand();
+                    let value = self.pop();
+                    *self.current_frame().locals[index].borrow_mut() = Some(value);
+                }
+                Opcode::OpGetLocal => {
+                    let index = self.read_u8_operand();
+                    let frame = self.current_frame();
+                    let value = frame.locals[index].borrow().clone();
+                    let value = value.ok_or_else(|| {
+                        format!(
+                            "identifier not found: {}",
+                            frame.closure.function.local_names[index]
+                        )
+                    })?;
+                    self.push(value)?;
+                }
+                Opcode::OpGetBuiltin => {
+                    let index = self.read_u8_operand();
+                    let (name, function) = BUILTINS[index];
+                    self.push(Object::Builtin { name, function })?;
+                }
+                Opcode::OpGetFree => {
+                    let index = self.read_u8_operand();
+                    let closure = &self.current_frame().closure;
+                    let value = closure.free[index].borrow().clone();
+                    let value = value.ok_or_else(|| {
+                        format!(
+                            "identifier not found: {}",
+                            closure.function.free_names[index]
+                        )
+                    })?;
+                    self.push(value)?;
+                }
+                Opcode::OpCurrentClosure => {
+                    let closure = self.current_frame().closure.clone();
+                    self.push(Object::Closure(closure))?;
+                }
+                Opcode::OpArray => {
+                    let count = self.read_u16_operand();
+                    let elements = self.stack[self.sp - count..self.sp].to_vec();
+                    self.sp -= count;
+                    self.push(Object::Array(elements))?;
+                }
+                Opcode::OpHash => {
+                    let count = self.read_u16_operand();
+                    let hash = self.build_hash(self.sp - count, self.sp)?;
+                    self.sp -= count;
+                    self.push(hash)?;
+                }
+                Opcode::OpIndex => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    self.push_result(eval_index_expression(left, index))?;
+                }
+                Opcode::OpCall => {
+                    let num_arguments = self.read_u8_operand();
+                    self.call(num_arguments)?;
+                }
+                Opcode::OpReturnValue => {
+                    let value = self.pop();
+                    if self.frames.len() == 1 {
+                        // A `return` outside of any function ends the program with its value.
+                        self.stack[self.sp] = value;
+                        return Ok(());
+                    }
+                    let frame = self.frames.pop().expect("a frame to return from");
+                    self.sp = frame.base_pointer - 1;
+                    self.push(value)?;
+                }
+                Opcode::OpReturn => {
+                    let frame = self.frames.pop().expect("a frame to return from");
+                    self.sp = frame.base_pointer - 1;
+                    self.push(Object::Null)?;
+                }
+                Opcode::OpClosure => {
+                    let index = self.read_u16_operand();
+                    let num_free = self.read_u8_operand();
+                    self.push_closure(index, num_free)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Where in the source the instruction that `run` failed on comes from, if the
+    /// compiler recorded it.
+    pub fn position(&self) -> Option<Span> {
+        let positions = &self.current_frame().closure.function.positions;
+        positions
+            .binary_search_by_key(&self.instruction, |(offset, _)| *offset)
+            .ok()
+            .map(|index| positions[index].1)
+    }
+
+    fn infix_operator(op: Opcode) -> &'static str {
+        match op {
+            Opcode::OpAdd => "+",
+            Opcode::OpSub => "-",
+            Opcode::OpMul => "*",
+            Opcode::OpDiv => "/",
+            Opcode::OpEqual => "==",
+            Opcode::OpNotEqual => "!=",
+            Opcode::OpGreaterThan => ">",
+            Opcode::OpLessThan => "<",
+            _ => unreachable!("{:?} is not an infix operator", op),
+        }
+    }
+
+    fn build_hash(&self, start: usize, end: usize) -> Result<Object, String> {
+        let mut hash = BTreeMap::new();
+
+        for pair in self.stack[start..end].chunks(2) {
+            let (key, value) = (pair[0].clone(), pair[1].clone());
+            let hash_key = key
+                .hash_key()
+                .ok_or_else(|| format!("unusable as hash key: {}", key.type_name()))?;
+            hash.insert(hash_key, (key, value));
+        }
+        Ok(Object::Hash(hash))
+    }
+
+    fn call(&mut self, num_arguments: usize) -> Result<(), String> {
+        match self.stack[self.sp - 1 - num_arguments].clone() {
+            Object::Closure(closure) => self.call_closure(closure, num_arguments),
+            Object::Builtin { function, .. } => {
+                let arguments = self.stack[self.sp - num_arguments..self.sp].to_vec();
+                let result = function(arguments);
+                self.sp = self.sp - num_arguments - 1;
+                self.push_result(result)
+            }
+            x => Err(format!("not a function: {}", x.type_name())),
+        }
+    }
+
+    fn call_closure(&mut self, closure: Rc<Closure>, num_arguments: usize) -> Result<(), String> {
+        if num_arguments != closure.function.num_parameters {
+            return Err(format!(
+                "wrong number of arguments: want={}, got={}",
+                closure.function.num_parameters, num_arguments
+            ));
+        }
+        if self.frames.len() >= MAX_FRAMES {
+            return Err("stack overflow".to_string());
+        }
+
+        // The arguments move off the stack into the first locals. The other locals
+        // stay unbound until their `let` runs.
+        let base_pointer = self.sp - num_arguments;
+        let mut locals: Vec<Variable> = self.stack[base_pointer..self.sp]
+            .iter()
+            .map(|argument| Rc::new(RefCell::new(Some(argument.clone()))))
+            .collect();
+        locals.resize_with(closure.function.local_names.len(), Variable::default);
+
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base_pointer,
+            locals,
+        });
+        self.sp = base_pointer;
+        Ok(())
+    }
+
+    fn push_closure(&mut self, index: usize, num_free: usize) -> Result<(), String> {
+        let function = match &self.constants[index] {
+            Object::CompiledFunction(function) => function.clone(),
+            x => return Err(format!("not a function: {}", x.type_name())),
+        };
+        let mut free = Vec::with_capacity(num_free);
+        for _ in 0..num_free {
+            free.push(self.read_capture()?);
+        }
+        self.push(Object::Closure(Rc::new(Closure { function, free })))
+    }
+
+    /// Reads one of the instructions following OpClosure, which names a variable of the
+    /// current frame to capture. The closure shares it rather than copying its value.
+    fn read_capture(&mut self) -> Result<Variable, String> {
+        let ip = self.current_frame().ip;
+        let op = Opcode::from_byte(self.current_instructions()[ip]);
+        self.current_frame_mut().ip += 1;
+
+        match op {
+            Some(Opcode::OpGetLocal) => {
+                let index = self.read_u8_operand();
+                Ok(self.current_frame().locals[index].clone())
+            }
+            Some(Opcode::OpGetFree) => {
+                let index = self.read_u8_operand();
+                Ok(self.current_frame().closure.free[index].clone())
+            }
+            Some(Opcode::OpCurrentClosure) => {
+                let closure = self.current_frame().closure.clone();
+                Ok(Rc::new(RefCell::new(Some(Object::Closure(closure)))))
+            }
+            op => Err(format!("cannot capture with {:?}", op)),
+        }
+    }
+
+    /// Pushes the result of an operation, turning an error object into a runtime error.
+    fn push_result(&mut self, result: Object) -> Result<(), String> {
+        match result {
+            Object::Error(message, _) => Err(message),
+            result => self.push(result),
+        }
+    }
+
+    fn push(&mut self, object: Object) -> Result<(), String> {
+        if self.sp >= STACK_SIZE {
+            return Err("stack overflow".to_string());
+        }
+        self.stack[self.sp] = object;
+        self.sp += 1;
+        Ok(())
+    }
+
+    /// Leaves the popped value in its slot, for `last_popped_stack_elem`.
+    fn pop(&mut self) -> Object {
+        self.sp -= 1;
+        self.stack[self.sp].clone()
+    }
+
+    fn read_u16_operand(&mut self) -> usize {
+        let ip = self.current_frame().ip;
+        let operand = read_u16(self.current_instructions(), ip) as usize;
+        self.current_frame_mut().ip += 2;
+        operand
+    }
+
+    fn read_u8_operand(&mut self) -> usize {
+        let ip = self.current_frame().ip;
+        let operand = self.current_instructions()[ip] as usize;
+        self.current_frame_mut().ip += 1;
+        operand
+    }
+
+    fn current_instructions(&self) -> &[u8] {
+        &self.current_frame().closure.function.instructions
+    }
+
+    fn current_frame(&self) -> &Frame {
+        self.frames.last().expect("the main frame is never popped")
+    }
+
+    fn current_frame_mut(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("the main frame is never popped")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::object::Object;
+    use crate::parser::Parser;
+    use crate::vm::VM;
+
+    fn run(input: &str) -> Result<Object, String> {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        let program = parser.parse_program().unwrap();
+        let mut compiler = Compiler::new();
+        compiler.compile(&program)?;
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run()?;
+        Ok(vm.last_popped_stack_elem())
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let tests = [
+            ("1", "1"),
+            ("1 + 2", "3"),
+            ("50 / 2 * 2 + 10 - 5", "55"),
+            ("-50 + 100 + -50", "0"),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", "50"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_calling_functions() {
+        let tests = [
+            ("let fivePlusTen = fn() { 5 + 10; }; fivePlusTen();", "15"),
+            ("let early = fn() { return 99; 100; }; early();", "99"),
+            ("let noReturn = fn() { }; noReturn();", "null"),
+            (
+                "let globalSeed = 50; let minusOne = fn() { let num = 1; globalSeed - num; }; minusOne();",
+                "49",
+            ),
+            ("let sum = fn(a, b) { let c = a + b; c; }; sum(1, 2) + sum(3, 4);", "10"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = [
+            (
+                "let newClosure = fn(a) { fn() { a; }; }; let closure = newClosure(99); closure();",
+                "99",
+            ),
+            (
+                r#"
+                let newAdderOuter = fn(a, b) {
+                    let c = a + b;
+                    fn(d) {
+                        let e = d + c;
+                        fn(f) { e + f; };
+                    };
+                };
+                let newAdderInner = newAdderOuter(1, 2);
+                let adder = newAdderInner(3);
+                adder(8);
+                "#,
+                "14",
+            ),
+            (
+                r#"
+                let wrapper = fn() {
+                    let countDown = fn(x) { if (x == 0) { return 0; } else { countDown(x - 1); } };
+                    countDown(1);
+                };
+                wrapper();
+                "#,
+                "0",
+            ),
+            ("let later = fn() { value }; let value = 7; later();", "7"),
+            (
+                "fn() { let x = 1; let f = fn() { x }; let x = 2; f() }()",
+                "2",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let tests = [
+            (
+                "fn() { 1; }(1);",
+                "wrong number of arguments: want=0, got=1",
+            ),
+            ("1(1)", "not a function: INTEGER"),
+            (
+                r#"len(1); 2"#,
+                "argument to `len` not supported, got INTEGER",
+            ),
+            ("{fn() {}: 1}", "unusable as hash key: FUNCTION"),
+            ("let f = fn(x) { f(x + 1) }; f(0)", "stack overflow"),
+            (
+                "let later = fn() { value }; later(); let value = 1;",
+                "identifier not found: value",
+            ),
+            (
+                "fn() { if (false) { let y = 1; } y }()",
+                "identifier not found: y",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input).unwrap_err(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_globals_store_across_runs() {
+        let mut compiler = Compiler::new();
+        let mut globals = None;
+
+        for (input, expected) in [("let a = 1; a", "1"), ("let b = a + 1; b * 10", "20")] {
+            let mut parser = Parser::new(Lexer::new(input.to_string()));
+            let program = parser.parse_program().unwrap();
+            compiler.compile(&program).unwrap();
+
+            let mut vm = match globals.take() {
+                Some(globals) => VM::new_with_globals_store(compiler.bytecode(), globals),
+                None => VM::new(compiler.bytecode()),
+            };
+            vm.run().unwrap();
+            assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+            globals = Some(vm.into_globals_store());
+
+            let (symbol_table, constants) = compiler.into_state();
+            compiler = Compiler::new_with_state(symbol_table, constants);
+        }
+    }
+}
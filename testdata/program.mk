@@ -0,0 +1,6 @@
+// Golden input for the JSON encoding of the AST.
+let fibonacci = fn(n) {
+    if (n < 2) { n } else { fibonacci(n - 1) + fibonacci(n - 2) }
+};
+let config = {"name": "monkey", "tags": ["a", "b"], 1: !true};
+return fibonacci(config["tags"][-1]);